@@ -1,7 +1,13 @@
-use crate::util;
-use clap::{Parser, Subcommand};
+use crate::components::Missing;
+use crate::error::MentalError;
+use crate::format::OutputFormat;
+use crate::hook::Shell;
+use crate::lint::ReportFormat;
+use crate::quoting::Quoting;
+use crate::util::{self, Discovery};
+use clap::{Args, Parser, Subcommand};
 use inquire::error::InquireResult;
-use inquire::MultiSelect;
+use inquire::{MultiSelect, Password, PasswordDisplayMode, Select, Text};
 use std::path::{Path, PathBuf};
 
 pub(crate) fn format_multiline_list(
@@ -11,13 +17,48 @@ pub(crate) fn format_multiline_list(
     MultiSelect::new(message, options).prompt()
 }
 
-pub(crate) fn folder_multiselect(folder_path: &Path) -> InquireResult<Vec<String>> {
-    let options = util::list_folders(folder_path);
+pub(crate) fn folder_multiselect(
+    folder_path: &Path,
+    discovery: &Discovery,
+) -> Result<Vec<String>, MentalError> {
+    let options = util::list_folders(folder_path, discovery)?;
     let folders_as_string: Vec<String> = options
         .into_iter()
         .filter_map(|f| f.into_os_string().into_string().ok())
         .collect();
-    format_multiline_list(folders_as_string, "Select target folders:")
+    Ok(format_multiline_list(
+        folders_as_string,
+        "Select target folders:",
+    )?)
+}
+
+pub(crate) fn select(options: Vec<String>, message: &str) -> InquireResult<String> {
+    Select::new(message, options).prompt()
+}
+
+/// Ask for a required value that is missing, sensitive values are masked
+///
+/// * `missing`: value to ask for
+pub(crate) fn prompt_missing(missing: &Missing) -> InquireResult<String> {
+    let message = format!("{} ({}):", missing.name, missing.component);
+    match missing.sensitive {
+        true => {
+            let prompt = Password::new(&message)
+                .without_confirmation()
+                .with_display_mode(PasswordDisplayMode::Masked);
+            match &missing.description {
+                Some(description) => prompt.with_help_message(description).prompt(),
+                None => prompt.prompt(),
+            }
+        }
+        false => {
+            let prompt = Text::new(&message);
+            match &missing.description {
+                Some(description) => prompt.with_help_message(description).prompt(),
+                None => prompt.prompt(),
+            }
+        }
+    }
 }
 
 pub(crate) fn module_multiselect(
@@ -27,9 +68,30 @@ pub(crate) fn module_multiselect(
     format_multiline_list(components, message)
 }
 
+/// Split a `KEY=VALUE` argument at the first `=`
+///
+/// * `argument`: raw argument
+fn parse_key_value(argument: &str) -> Result<(String, String), String> {
+    match argument.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got '{}'", argument)),
+    }
+}
+
+/// Exit codes listed in the help
+const EXIT_CODES: &str = "Exit codes:
+  0    success
+  1    generated files differ from the files on disk (apply --check)
+  2    invalid command line arguments
+  3    a config, mapping or .env file could not be parsed
+  4    the config, a mapping or the given values are invalid
+  5    reading or writing a file failed
+  6    a change conflicts with existing data, e.g. a duplicate name
+  130  a prompt was aborted";
+
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
-#[command(arg_required_else_help = true)]
+#[command(arg_required_else_help = true, after_help = EXIT_CODES)]
 pub(crate) struct Cli {
     /// Sets a custom config file
     #[arg(short, long, value_name = "FILE")]
@@ -50,20 +112,242 @@ pub(crate) enum Commands {
         /// only print to stdout
         #[arg(short, long, action)]
         stdout: bool,
+
+        /// only check if the files on disk are up to date, exits with 1 on drift
+        #[arg(long, action, conflicts_with = "stdout")]
+        check: bool,
+
+        /// only print a diff of the changes that would be made, secrets are masked
+        #[arg(long, action, conflicts_with_all = ["stdout", "check"])]
+        diff: bool,
+
+        /// show the values of sensitive variables with --stdout and --diff
+        #[arg(long, action)]
+        reveal: bool,
+
+        /// format of mappings that do not configure one [default: dotenv]
+        #[arg(short, long, value_enum)]
+        format: Option<OutputFormat>,
+
+        /// quoting dialect of the tool reading the .env file
+        #[arg(short, long, value_enum, default_value_t)]
+        quoting: Quoting,
+
+        /// profile whose overrides are applied
+        #[arg(short, long)]
+        profile: Option<String>,
+
+        /// write one file per profile, e.g. `.env.staging` or `settings.staging.json`, fails if there are no profiles
+        #[arg(short, long, action, conflicts_with_all = ["profile", "stdout"])]
+        each_profile: bool,
+    },
+    /// Run a command with the variables of components in its environment
+    Run {
+        /// components whose variables are injected
+        #[arg(long, num_args = 1.., required_unless_present = "mapping")]
+        components: Vec<String>,
+
+        /// mapping file to take the components of `target` from
+        #[arg(short, long, requires = "target", conflicts_with = "components")]
+        mapping: Option<PathBuf>,
+
+        /// target of the mapping
+        #[arg(short, long)]
+        target: Option<PathBuf>,
+
+        /// profile whose overrides are applied
+        #[arg(short, long)]
+        profile: Option<String>,
+
+        /// variables already set in the environment win over the components
+        #[arg(short, long, action)]
+        keep_existing: bool,
+
+        /// command to run, given after `--`
+        #[arg(last = true, required = true, value_name = "COMMAND")]
+        command: Vec<String>,
+    },
+    /// Print a shell snippet loading the variables of a target when entering its folder
+    Hook {
+        /// shell to print the snippet for
+        #[arg(value_enum)]
+        shell: Shell,
+
+        /// mapping files to look up targets in, defaults to the `*.map` files next to the config
+        #[arg(short, long, num_args = 1..)]
+        mapping: Vec<PathBuf>,
+
+        /// profile whose overrides are applied
+        #[arg(short, long)]
+        profile: Option<String>,
+    },
+    /// Print the shell code switching to the variables of the current folder
+    #[command(hide = true)]
+    HookEnv {
+        #[arg(value_enum)]
+        shell: Shell,
+
+        #[arg(short, long, num_args = 1..)]
+        mapping: Vec<PathBuf>,
+
+        #[arg(short, long)]
+        profile: Option<String>,
+    },
+    /// Check mapping files against the config, exits with 4 if there are problems
+    Validate {
+        /// mapping files to check, defaults to the `*.map` files next to the config
+        mapping: Vec<PathBuf>,
+    },
+    /// Report problems in the config, exits with 4 if there are errors
+    #[command(alias = "doctor")]
+    Lint {
+        #[arg(short, long, value_enum, default_value_t)]
+        format: ReportFormat,
+
+        /// mapping files checked for unused components, defaults to the `*.map` files next to the config
+        #[arg(short, long, num_args = 1..)]
+        mapping: Vec<PathBuf>,
     },
     /// Map components to targets
-    Map { target: Option<PathBuf> },
+    #[command(args_conflicts_with_subcommands = true)]
+    Map {
+        #[command(subcommand)]
+        action: Option<MapAction>,
+
+        /// folder to search for targets when creating a mapping interactively
+        target: Option<PathBuf>,
+
+        #[command(flatten)]
+        discovery: DiscoveryArgs,
+    },
     /// Dump Schema
     Schema {
         /// Sets a custom config file
         #[arg(short, long, value_name = "PATH")]
         target: Option<PathBuf>,
+
+        /// dump the schema of mapping files instead of the config
+        #[arg(short, long, action)]
+        mapping: bool,
     },
     /// List components
     Component {
         #[command(subcommand)]
         component: Component,
     },
+    /// Manage the key secrets are encrypted with
+    Secrets {
+        #[command(subcommand)]
+        secrets: Secrets,
+    },
+}
+
+/// Options of the search for target folders offered by the prompts
+#[derive(Args, Debug)]
+pub(crate) struct DiscoveryArgs {
+    /// how many levels of subfolders are searched for targets
+    #[arg(long, default_value_t = 1)]
+    depth: usize,
+
+    /// only offer folders matching the glob, e.g. `services/*`, can be repeated
+    #[arg(long)]
+    include: Vec<String>,
+
+    /// skip folders matching the glob and everything below them, can be repeated
+    #[arg(long)]
+    exclude: Vec<String>,
+
+    /// only offer folders containing the file, e.g. `Cargo.toml`, can be repeated
+    #[arg(long)]
+    marker: Vec<String>,
+}
+
+impl DiscoveryArgs {
+    /// Options of the search
+    pub(crate) fn discovery(&self) -> Discovery {
+        Discovery {
+            depth: self.depth,
+            include: self.include.clone(),
+            exclude: self.exclude.clone(),
+            markers: self.marker.clone(),
+        }
+    }
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum MapAction {
+    /// List the targets of a mapping file
+    List {
+        /// mapping file, prompted for if there are several next to the config
+        #[arg(short, long)]
+        mapping: Option<PathBuf>,
+    },
+
+    /// Map components to a new target, creates the mapping file if needed
+    Add {
+        /// target folder, prompted for if missing
+        path: Option<PathBuf>,
+
+        /// components mapped to the target, prompted for if missing
+        #[arg(long, num_args = 1..)]
+        components: Vec<String>,
+
+        /// file the variables are written into, relative to the target
+        #[arg(long)]
+        file: Option<PathBuf>,
+
+        /// mapping file, prompted for if there are several next to the config
+        #[arg(short, long)]
+        mapping: Option<PathBuf>,
+
+        #[command(flatten)]
+        discovery: DiscoveryArgs,
+    },
+
+    /// Remove a target from a mapping file
+    Remove {
+        /// target folder, prompted for if missing
+        path: Option<PathBuf>,
+
+        /// only remove the mapping into this file, relative to the target
+        #[arg(long)]
+        file: Option<PathBuf>,
+
+        /// mapping file, prompted for if there are several next to the config
+        #[arg(short, long)]
+        mapping: Option<PathBuf>,
+    },
+
+    /// Replace the components mapped to a target
+    SetComponents {
+        /// target folder, prompted for if missing
+        path: Option<PathBuf>,
+
+        /// components mapped to the target, prompted for if missing
+        #[arg(long, num_args = 1..)]
+        components: Vec<String>,
+
+        /// only update the mapping into this file, relative to the target
+        #[arg(long)]
+        file: Option<PathBuf>,
+
+        /// mapping file, prompted for if there are several next to the config
+        #[arg(short, long)]
+        mapping: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub(crate) enum Secrets {
+    /// Create a key file next to the config, or at `MENTAL_KEY_FILE`
+    GenerateKey {},
+
+    /// Encrypt all secrets with a new key file
+    RotateKey {
+        /// prompt for a new passphrase instead of writing a key file, removes the old key file
+        #[arg(long, action)]
+        passphrase: bool,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -76,6 +360,22 @@ pub(crate) enum Component {
         /// Components to show (whitespace sperated)
         #[clap(value_parser, num_args =1.., value_delimiter = ' ', required=true)]
         names: Vec<String>,
+
+        /// format to render the variables into
+        #[arg(short, long, value_enum, default_value_t)]
+        format: OutputFormat,
+
+        /// quoting dialect of the tool reading the .env file
+        #[arg(short, long, value_enum, default_value_t)]
+        quoting: Quoting,
+
+        /// profile whose overrides are applied
+        #[arg(short, long)]
+        profile: Option<String>,
+
+        /// show the values of sensitive variables instead of masking them
+        #[arg(long, action)]
+        reveal: bool,
     },
 
     /// Create a new component
@@ -84,19 +384,112 @@ pub(crate) enum Component {
         #[arg(value_name = "name")]
         name: String,
 
-        /// optional prefix
-        #[arg(value_name = "prefix")]
+        /// values of the component, a value may contain spaces if it is quoted
+        #[arg(value_name = "KEY=VALUE", value_parser = parse_key_value)]
+        values: Vec<(String, String)>,
+
+        /// store the given values as references written as `file:PATH`, `env:NAME` or
+        /// `cmd:COMMAND`
+        #[arg(long = "ref", action)]
+        reference: bool,
+
+        /// prefix put in front of the keys
+        #[arg(short, long)]
         prefix: Option<String>,
 
-        /// keys, seperated by whitespace
-        #[clap(short, long, value_parser, num_args = 1.., value_delimiter = ' ', required=true)]
+        /// read values from a .env file, values given as arguments take precedence
+        #[arg(long, conflicts_with = "from_stdin")]
+        from_file: Option<PathBuf>,
+
+        /// read values in .env syntax from standard input
+        #[arg(long, action)]
+        from_stdin: bool,
+    },
+
+    /// Set values of a component, replacing values with the same name
+    Set {
+        /// Name of the component
+        component: String,
+
+        /// values to set
+        #[arg(value_name = "KEY=VALUE", num_args = 1.., required = true, value_parser = parse_key_value)]
+        values: Vec<(String, String)>,
+
+        /// store the values encrypted
+        #[arg(short, long, action)]
+        secret: bool,
+
+        /// store the values as references written as `file:PATH`, `env:NAME` or
+        /// `cmd:COMMAND`
+        #[arg(long = "ref", action, conflicts_with = "secret")]
+        reference: bool,
+    },
+
+    /// Remove values of a component together with their profile overrides
+    Unset {
+        /// Name of the component
+        component: String,
+
+        /// keys to remove, without the prefix
+        #[arg(num_args = 1.., required = true)]
         keys: Vec<String>,
+    },
+
+    /// Set the prefix of a component, removes it if omitted
+    SetPrefix {
+        /// Name of the component
+        component: String,
+
+        /// new prefix, "" also clears a prefix inherited from `extends`
+        prefix: Option<String>,
+    },
+
+    /// Delete a component that is neither extended nor mapped
+    Delete {
+        /// Name of the component
+        component: String,
 
-        /// values, seperated by whitespace
-        #[clap(short, long, value_parser, num_args = 1.., value_delimiter = ' ', required=true)]
-        values: Vec<String>,
+        /// mapping files checked for the component, defaults to the `*.map` files next to the config
+        #[arg(short, long, num_args = 1..)]
+        mapping: Vec<PathBuf>,
+    },
+
+    /// Rename a component and update the components extending it and the mapping files
+    Rename {
+        /// current name of the component
+        component: String,
+
+        /// new name of the component
+        name: String,
+
+        /// mapping files to update, defaults to the `*.map` files next to the config
+        #[arg(short, long, num_args = 1..)]
+        mapping: Vec<PathBuf>,
+    },
+
+    /// Copy a component under a new name
+    Copy {
+        /// Name of the component to copy
+        source: String,
+
+        /// Name of the copy
+        destination: String,
     },
 
     /// Create a component from current environment
     FromEnv {},
+
+    /// Create a component from an existing .env file
+    Import {
+        /// .env file to import
+        file: PathBuf,
+
+        /// Name of the component, prompted for if missing
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// prefix shared by all keys, stripped from the keys and stored as prefix
+        #[arg(short, long)]
+        prefix: Option<String>,
+    },
 }
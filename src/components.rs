@@ -1,67 +1,378 @@
 //! Structs and implementation to handle components
 
+use crate::error::MentalError;
+use crate::references::Reference;
+use crate::rules::Rules;
+use crate::secrets::Secret;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
 
-#[derive(Debug, Deserialize, Serialize, JsonSchema)]
+/// Words in names of variables that usually hold secrets
+const SENSITIVE_NAMES: [&str; 4] = ["PASSWORD", "SECRET", "TOKEN", "KEY"];
+
+/// Value shown instead of a sensitive value
+pub(crate) const MASK: &str = "********";
+
+/// Check if the name of a variable looks like it holds a secret
+///
+/// Only whole words separated by `_` count, so `API_KEY` is sensitive but `MONKEY` is not.
+///
+/// * `name`: name of the variable
+pub(crate) fn looks_sensitive(name: &str) -> bool {
+    name.to_uppercase()
+        .split('_')
+        .any(|word| SENSITIVE_NAMES.contains(&word))
+}
+
+/// Check if a name can be used as a variable in POSIX shells
+///
+/// * `name`: name of the variable
+pub(crate) fn is_posix_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Separator lists are joined with if the value does not set one
+const DEFAULT_SEPARATOR: &str = ",";
+
+/// Value of a variable
+///
+/// Scalars, lists and objects keep their type in the JSON and YAML outputs. Line based
+/// formats write lists joined by the separator of the value and objects as JSON.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
 #[serde(untagged)]
-pub enum StringOrInt {
-    Integer(u32),
+pub enum Value {
+    Bool(bool),
+    Integer(i64),
+    Float(f64),
     String(String),
+    Secret(Secret),
+    Reference(Reference),
+    List(Vec<serde_json::Value>),
+    Object(serde_json::Map<String, serde_json::Value>),
+}
+
+impl Value {
+    /// Value as it is put into the environment, without any quoting
+    ///
+    /// Secrets are returned encrypted and references as written, both are replaced while
+    /// resolving the sections.
+    ///
+    /// * `separator`: separator lists are joined with, defaults to `,`
+    pub(crate) fn to_raw_string(&self, separator: Option<&str>) -> String {
+        match self {
+            Value::Bool(v) => v.to_string(),
+            Value::Integer(v) => v.to_string(),
+            Value::Float(v) => format_float(*v),
+            Value::String(v) => v.clone(),
+            Value::Secret(v) => v.encoded().to_string(),
+            Value::Reference(v) => v.to_string(),
+            Value::List(items) => items
+                .iter()
+                .map(|item| match item {
+                    serde_json::Value::String(v) => v.clone(),
+                    v => v.to_string(),
+                })
+                .collect::<Vec<String>>()
+                .join(separator.unwrap_or(DEFAULT_SEPARATOR)),
+            Value::Object(v) => serde_json::Value::Object(v.clone()).to_string(),
+        }
+    }
+
+    /// Check if the value is written without quotes by line based formats
+    pub(crate) fn is_bare(&self) -> bool {
+        matches!(self, Value::Bool(_) | Value::Integer(_) | Value::Float(_))
+    }
+
+    /// Parse a value given on the command line
+    ///
+    /// Booleans and numbers are only typed if they are written the way they are rendered,
+    /// so `007` or `1e3` stay strings.
+    ///
+    /// * `value`: raw value
+    pub(crate) fn parse(value: String) -> Value {
+        let typed = match value.as_str() {
+            "true" => Some(Value::Bool(true)),
+            "false" => Some(Value::Bool(false)),
+            v => v.parse::<i64>().map(Value::Integer).ok().or_else(|| {
+                v.parse::<f64>()
+                    .ok()
+                    .filter(|f| f.is_finite())
+                    .map(Value::Float)
+            }),
+        };
+        match typed {
+            Some(typed) if typed.to_raw_string(None) == value => typed,
+            _ => Value::String(value),
+        }
+    }
+
+    /// Parse a reference given on the command line as `file:PATH`, `env:NAME` or `cmd:COMMAND`
+    ///
+    /// Only used if references are asked for explicitly, so values like `file:./dev.db`
+    /// stay literal otherwise.
+    ///
+    /// * `value`: reference the way references are shown
+    pub(crate) fn parse_reference(value: String) -> Result<Value, MentalError> {
+        let reference = match value.split_once(':') {
+            Some(("file", file)) if !file.is_empty() => Reference::File { file: file.into() },
+            Some(("env", env)) if !env.is_empty() => Reference::Env { env: env.into() },
+            Some(("cmd", cmd)) if !cmd.is_empty() => Reference::Cmd {
+                cmd: cmd.into(),
+                timeout: None,
+            },
+            _ => {
+                return Err(MentalError::Validation(format!(
+                    "'{}' is no reference, expected file:PATH, env:NAME or cmd:COMMAND",
+                    value
+                )))
+            }
+        };
+        Ok(Value::Reference(reference))
+    }
+}
+
+/// Format a float the shortest way it is read back as the same number
+///
+/// * `value`: float to format
+fn format_float(value: f64) -> String {
+    match serde_json::Number::from_f64(value) {
+        Some(number) => number.to_string(),
+        None if value.is_nan() => "nan".into(),
+        None if value > 0.0 => "inf".into(),
+        None => "-inf".into(),
+    }
 }
 
 /// Struct holding the key and values
 ///
-/// * `name`: name of the value
-/// * `value`: value
-#[derive(Debug, Serialize, Deserialize, JsonSchema)]
-struct KeyValue {
+/// Values of a profile or of a component extending another one are laid over the value
+/// with the same name, fields they leave out are taken from it.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub(crate) struct KeyValue {
+    /// name of the value without the prefix of the component
     name: String,
-    value: StringOrInt,
+    /// value, the default is used if it is missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    value: Option<Value>,
+    /// what the variable is used for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    /// fail if neither a value nor a default is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    required: Option<bool>,
+    /// value used if no value is set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    default: Option<Value>,
+    /// example of a valid value, for documentation only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    example: Option<Value>,
+    /// rules the value is checked against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    rules: Option<Rules>,
+    /// mask the value in output, detected from the name and secrets if unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    sensitive: Option<bool>,
+    /// separator a list is joined with in line based formats, defaults to `,`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    separator: Option<String>,
 }
 
 impl KeyValue {
-    /// Format the key value to .env format
-    fn to_env(&self) -> String {
-        match &self.value {
-            StringOrInt::String(v) => {
-                format!(r#"{0}="{1}""#, self.name, v.to_owned())
-            }
-            StringOrInt::Integer(v) => {
-                let value_as_string = v.to_string();
-                format!(r#"{0}={1}"#, self.name, value_as_string)
-            }
+    /// Create a value without metadata
+    ///
+    /// * `name`: name of the value without the prefix
+    /// * `value`: value
+    fn new(name: String, value: Value) -> KeyValue {
+        KeyValue {
+            name,
+            value: Some(value),
+            description: None,
+            required: None,
+            default: None,
+            example: None,
+            rules: None,
+            sensitive: None,
+            separator: None,
+        }
+    }
+
+    /// Lay another value with the same name over this one
+    ///
+    /// * `over`: value whose fields take precedence
+    fn overlay(&self, over: &KeyValue) -> KeyValue {
+        KeyValue {
+            name: over.name.clone(),
+            value: over.value.clone().or_else(|| self.value.clone()),
+            description: over
+                .description
+                .clone()
+                .or_else(|| self.description.clone()),
+            required: over.required.or(self.required),
+            default: over.default.clone().or_else(|| self.default.clone()),
+            example: over.example.clone().or_else(|| self.example.clone()),
+            rules: over.rules.clone().or_else(|| self.rules.clone()),
+            sensitive: over.sensitive.or(self.sensitive),
+            separator: over.separator.clone().or_else(|| self.separator.clone()),
         }
     }
+
+    /// Value put into the environment, the default if no value is set
+    fn effective_value(&self) -> Option<&Value> {
+        self.value.as_ref().or(self.default.as_ref())
+    }
+}
+
+/// Variable ready to be rendered
+///
+/// * `name`: name of the variable including the prefix
+/// * `value`: value
+/// * `sensitive`: if the value is masked in output
+/// * `separator`: separator a list is joined with
+/// * `rules`: rules the value is checked against
+/// * `required`: if an empty value counts as missing
+#[derive(Debug, Clone)]
+pub(crate) struct EnvVar {
+    pub(crate) name: String,
+    pub(crate) value: Value,
+    pub(crate) sensitive: bool,
+    pub(crate) separator: Option<String>,
+    pub(crate) rules: Option<Rules>,
+    pub(crate) required: bool,
+}
+
+impl EnvVar {
+    /// Create a variable that is not sensitive, not required and has no rules
+    ///
+    /// * `name`: name of the variable including the prefix
+    /// * `value`: value
+    pub(crate) fn new(name: &str, value: Value) -> EnvVar {
+        EnvVar {
+            name: name.to_string(),
+            value,
+            sensitive: false,
+            separator: None,
+            rules: None,
+            required: false,
+        }
+    }
+
+    /// Value as it is put into the environment, without any quoting
+    pub(crate) fn raw_value(&self) -> String {
+        self.value.to_raw_string(self.separator.as_deref())
+    }
+}
+
+/// Required value that is not set or empty
+///
+/// * `component`: name of the component
+/// * `key`: name of the value without the prefix
+/// * `name`: name of the variable including the prefix
+/// * `description`: what the variable is used for
+/// * `sensitive`: if the value has to be entered without showing it
+#[derive(Debug, Clone)]
+pub(crate) struct Missing {
+    pub(crate) component: String,
+    pub(crate) key: String,
+    pub(crate) name: String,
+    pub(crate) description: Option<String>,
+    pub(crate) sensitive: bool,
 }
 
 /// Component struct
 ///
 /// * `name`: name of the component
-/// * `prefix`: optional prefix put in front of the variable
+/// * `prefix`: optional prefix put in front of the variable, inherited from `extends` if not
+///   set, `""` clears an inherited prefix
 /// * `values`: values under the component
-#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+/// * `profiles`: values overriding or extending `values` per profile
+/// * `extends`: components whose values are inherited, see [`Component::inherit`]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Component {
     pub(crate) name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     prefix: Option<String>,
+    #[serde(default)]
     values: Vec<KeyValue>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(crate) extends: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(crate) profiles: BTreeMap<String, Vec<KeyValue>>,
 }
 
 impl Component {
-    /// Format the component into the .env format
-    pub(crate) fn to_env(&self) -> Vec<String> {
-        let prefix_upper = match &self.prefix {
-            Some(x) => format!("{}_", x.to_uppercase()),
-            None => "".to_string(),
-        };
-        let mut formatted_values: Vec<String> = Vec::new();
-        let component_comment = format!("# component {}", &self.name);
-        formatted_values.push(component_comment);
-        for value in &self.values {
-            let value_with_prefix = format!(r#"{}{}"#, prefix_upper, value.to_env());
-            formatted_values.push(value_with_prefix);
+    /// Values of the component with the overrides of a profile laid over them
+    ///
+    /// Values of the profile override values with the same name, other values of the
+    /// profile are added at the end.
+    ///
+    /// * `profile`: profile whose overrides are applied
+    fn effective_values(&self, profile: Option<&str>) -> Vec<KeyValue> {
+        let mut values: Vec<KeyValue> = self.values.clone();
+        let overrides = profile.and_then(|p| self.profiles.get(p));
+        for value in overrides.into_iter().flatten() {
+            match values.iter().position(|v| v.name == value.name) {
+                Some(position) => values[position] = values[position].overlay(value),
+                None => values.push(value.clone()),
+            }
         }
-        formatted_values
+        values
+    }
+
+    /// Collect the variables of the component with the prefix applied
+    ///
+    /// Values without a value or default are left out, see [`Component::missing`].
+    ///
+    /// * `profile`: profile whose overrides are applied
+    pub(crate) fn variables(&self, profile: Option<&str>) -> Vec<EnvVar> {
+        let prefix_upper = self.applied_prefix();
+        self.effective_values(profile)
+            .into_iter()
+            .filter_map(|value| {
+                let name = format!(r#"{}{}"#, prefix_upper, value.name);
+                let effective = value.effective_value()?.clone();
+                let sensitive = value.sensitive.unwrap_or_else(|| {
+                    looks_sensitive(&name) || matches!(effective, Value::Secret(_))
+                });
+                Some(EnvVar {
+                    name,
+                    value: effective,
+                    sensitive,
+                    separator: value.separator,
+                    rules: value.rules,
+                    required: value.required == Some(true),
+                })
+            })
+            .collect()
+    }
+
+    /// Required values without a value or default, or whose value resolved to empty
+    ///
+    /// * `profile`: profile whose overrides are applied
+    /// * `empty`: names of the variables including the prefix that resolved to empty
+    pub(crate) fn missing(&self, profile: Option<&str>, empty: &[&str]) -> Vec<Missing> {
+        let prefix_upper = self.applied_prefix();
+        self.effective_values(profile)
+            .into_iter()
+            .filter(|v| v.required == Some(true))
+            .filter_map(|v| {
+                let name = format!("{}{}", prefix_upper, v.name);
+                if v.effective_value().is_some() && !empty.contains(&name.as_str()) {
+                    return None;
+                }
+                Some(Missing {
+                    component: self.name.clone(),
+                    sensitive: v.sensitive.unwrap_or_else(|| looks_sensitive(&name)),
+                    key: v.name,
+                    name,
+                    description: v.description,
+                })
+            })
+            .collect()
     }
 
     /// Create a component
@@ -72,23 +383,351 @@ impl Component {
     pub(crate) fn new(
         name: String,
         prefix: Option<String>,
-        values: Vec<(String, String)>,
+        values: Vec<(String, Value)>,
     ) -> Component {
         let mut given_key_values: Vec<KeyValue> = Vec::new();
         for (key, value) in values {
-            let parsed_value: StringOrInt = match value.parse::<u32>() {
-                Ok(v) => StringOrInt::Integer(v),
-                Err(v) => StringOrInt::String(v.to_string()),
-            };
-            given_key_values.push(KeyValue {
-                name: key,
-                value: parsed_value,
-            })
+            given_key_values.push(KeyValue::new(key, value))
         }
         Component {
             name,
             prefix,
             values: given_key_values,
+            extends: Vec::new(),
+            profiles: BTreeMap::new(),
         }
     }
+
+    /// Prefix put in front of the keys, upper cased with a trailing `_`
+    pub(crate) fn applied_prefix(&self) -> String {
+        match &self.prefix {
+            Some(x) if !x.is_empty() => format!("{}_", x.to_uppercase()),
+            _ => "".to_string(),
+        }
+    }
+
+    /// Keys of the values and of each profile, `None` stands for the values
+    pub(crate) fn key_lists(&self) -> Vec<(Option<&str>, Vec<&str>)> {
+        [(None, &self.values)]
+            .into_iter()
+            .chain(self.profiles.iter().map(|(p, v)| (Some(p.as_str()), v)))
+            .map(|(profile, values)| (profile, values.iter().map(|v| v.name.as_str()).collect()))
+            .collect()
+    }
+
+    /// Rules of the values and of each profile, `None` stands for the values
+    pub(crate) fn rules(&self) -> Vec<(Option<&str>, &str, &Rules)> {
+        [(None, &self.values)]
+            .into_iter()
+            .chain(self.profiles.iter().map(|(p, v)| (Some(p.as_str()), v)))
+            .flat_map(|(profile, values)| {
+                values
+                    .iter()
+                    .filter_map(move |v| Some((profile, v.name.as_str(), v.rules.as_ref()?)))
+            })
+            .collect()
+    }
+
+    /// Check if the component defines no values on its own and inherits none
+    pub(crate) fn is_empty(&self) -> bool {
+        self.values.is_empty()
+            && self.extends.is_empty()
+            && self.profiles.values().all(|v| v.is_empty())
+    }
+
+    /// Set a value, replacing an existing value with the same name
+    ///
+    /// * `name`: name of the value without the prefix
+    /// * `value`: new value
+    pub(crate) fn set_value(&mut self, name: String, value: Value) {
+        match self.values.iter_mut().find(|v| v.name == name) {
+            Some(existing) => existing.value = Some(value),
+            None => self.values.push(KeyValue::new(name, value)),
+        }
+    }
+
+    /// Set a value that takes precedence over the values of all profiles
+    ///
+    /// Names the component does not define are ignored, so removed values stay removed.
+    ///
+    /// * `name`: name of the value without the prefix
+    /// * `value`: new value
+    pub(crate) fn override_value(&mut self, name: &str, value: Value) {
+        let defined = self
+            .values
+            .iter()
+            .chain(self.profiles.values().flatten())
+            .any(|v| v.name == name);
+        if !defined {
+            return;
+        }
+        self.set_value(name.to_string(), value);
+        for values in self.profiles.values_mut() {
+            for v in values.iter_mut().filter(|v| v.name == name) {
+                v.value = None;
+                v.default = None;
+            }
+        }
+    }
+
+    /// Remove a value together with its profile overrides
+    ///
+    /// Returns `false` if neither the values nor a profile define the name.
+    ///
+    /// * `name`: name of the value without the prefix
+    pub(crate) fn unset_value(&mut self, name: &str) -> bool {
+        let count = self.values.len() + self.profiles.values().map(Vec::len).sum::<usize>();
+        self.values.retain(|v| v.name != name);
+        for values in self.profiles.values_mut() {
+            values.retain(|v| v.name != name);
+        }
+        self.profiles.retain(|_, values| !values.is_empty());
+        count != self.values.len() + self.profiles.values().map(Vec::len).sum::<usize>()
+    }
+
+    /// Replace the prefix, `None` removes it
+    ///
+    /// * `prefix`: new prefix
+    pub(crate) fn set_prefix(&mut self, prefix: Option<String>) {
+        self.prefix = prefix;
+    }
+
+    /// Iterate over all values and defaults of the component including the profile overrides
+    pub(crate) fn values_mut(&mut self) -> impl Iterator<Item = &mut Value> {
+        self.values
+            .iter_mut()
+            .chain(self.profiles.values_mut().flatten())
+            .flat_map(|v| v.value.iter_mut().chain(v.default.iter_mut()))
+    }
+
+    /// Iterate over the secrets of the component including defaults and profile overrides
+    pub(crate) fn secrets(&self) -> impl Iterator<Item = &Secret> {
+        self.values
+            .iter()
+            .chain(self.profiles.values().flatten())
+            .flat_map(|v| v.value.iter().chain(v.default.iter()))
+            .filter_map(|v| match v {
+                Value::Secret(secret) => Some(secret),
+                _ => None,
+            })
+    }
+
+    /// Merge the values of the parents into the component
+    ///
+    /// Values of the component override inherited values with the same name. The prefix is
+    /// inherited as well unless the component sets its own, an empty prefix clears it. Parents defining the same name
+    /// with different values, or different prefixes, are a conflict the component has to
+    /// resolve by overriding them.
+    ///
+    /// * `parents`: parents listed in `extends`, already merged with their own parents
+    pub(crate) fn inherit(&self, parents: &[Component]) -> Result<Component, MentalError> {
+        let prefix = match &self.prefix {
+            Some(prefix) => Some(prefix.clone()),
+            None => {
+                let prefixes: BTreeSet<&String> = parents
+                    .iter()
+                    .filter_map(|p| p.prefix.as_ref())
+                    .filter(|p| !p.is_empty())
+                    .collect();
+                if prefixes.len() > 1 {
+                    let prefixes: Vec<&str> = prefixes.into_iter().map(|p| p.as_str()).collect();
+                    return Err(MentalError::Validation(format!(
+                        "component '{}' inherits conflicting prefixes {}, set a prefix to resolve it",
+                        self.name,
+                        prefixes.join(", ")
+                    )));
+                }
+                prefixes.into_iter().next().cloned()
+            }
+        };
+
+        let inherited: Vec<(&str, &[KeyValue])> = parents
+            .iter()
+            .map(|p| (p.name.as_str(), p.values.as_slice()))
+            .collect();
+        let values = self.merge_values(&inherited, &self.values)?;
+
+        let profile_names: BTreeSet<&String> = parents
+            .iter()
+            .chain([self])
+            .flat_map(|c| c.profiles.keys())
+            .collect();
+        let mut profiles: BTreeMap<String, Vec<KeyValue>> = BTreeMap::new();
+        for profile in profile_names {
+            let inherited: Vec<(&str, &[KeyValue])> = parents
+                .iter()
+                .map(|p| {
+                    let values = p.profiles.get(profile).map_or(&[][..], |v| v.as_slice());
+                    (p.name.as_str(), values)
+                })
+                .collect();
+            let own = self.profiles.get(profile).map_or(&[][..], |v| v.as_slice());
+            profiles.insert(profile.clone(), self.merge_values(&inherited, own)?);
+        }
+
+        Ok(Component {
+            name: self.name.clone(),
+            prefix,
+            values,
+            extends: Vec::new(),
+            profiles,
+        })
+    }
+
+    /// Merge inherited values with the values of the component
+    ///
+    /// * `inherited`: values of each parent together with the name of the parent
+    /// * `own`: values of the component overriding the inherited ones
+    fn merge_values(
+        &self,
+        inherited: &[(&str, &[KeyValue])],
+        own: &[KeyValue],
+    ) -> Result<Vec<KeyValue>, MentalError> {
+        let mut merged: Vec<(&str, KeyValue)> = Vec::new();
+        for (parent, values) in inherited {
+            for value in values.iter() {
+                match merged.iter().find(|(_, v)| v.name == value.name) {
+                    None => merged.push((parent, value.clone())),
+                    Some((_, existing)) if existing.value == value.value => {}
+                    Some(_) if own.iter().any(|v| v.name == value.name) => {}
+                    Some((other, _)) => {
+                        return Err(MentalError::Validation(format!(
+                            "component '{}' inherits different values for '{}' from '{}' and '{}'",
+                            self.name, value.name, other, parent
+                        )))
+                    }
+                }
+            }
+        }
+        let mut values: Vec<KeyValue> = merged.into_iter().map(|(_, v)| v).collect();
+        for value in own {
+            match values.iter().position(|v| v.name == value.name) {
+                Some(position) => values[position] = values[position].overlay(value),
+                None => values.push(value.clone()),
+            }
+        }
+        Ok(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(yaml: &str) -> Value {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn objects_with_reference_keys_stay_objects() {
+        assert!(matches!(value("{env: HOME, region: eu}"), Value::Object(_)));
+        assert!(matches!(
+            value("{cmd: 'echo hi', other: 1}"),
+            Value::Object(_)
+        ));
+        assert!(matches!(value("{secret: abc, x: 1}"), Value::Object(_)));
+        assert!(matches!(value("{env: HOME}"), Value::Reference(_)));
+        assert!(matches!(
+            value("{cmd: 'echo hi', timeout: 3}"),
+            Value::Reference(_)
+        ));
+        assert!(matches!(value("{secret: abc}"), Value::Secret(_)));
+    }
+
+    #[test]
+    fn scalars_keep_their_type() {
+        assert_eq!(value("true"), Value::Bool(true));
+        assert_eq!(value("-1"), Value::Integer(-1));
+        assert_eq!(value("5000000000"), Value::Integer(5_000_000_000));
+        assert_eq!(value("0.5"), Value::Float(0.5));
+        assert_eq!(value("'007'"), Value::String("007".into()));
+    }
+
+    #[test]
+    fn empty_prefixes_clear_the_inherited_prefix() {
+        let parent: Component = serde_yaml::from_str("{name: db, prefix: pg}").unwrap();
+        let inheriting: Component = serde_yaml::from_str("{name: a, extends: [db]}").unwrap();
+        let clearing: Component =
+            serde_yaml::from_str("{name: b, prefix: '', extends: [db]}").unwrap();
+
+        let inherited = inheriting.inherit(std::slice::from_ref(&parent)).unwrap();
+        assert_eq!(inherited.applied_prefix(), "PG_");
+        let cleared = clearing.inherit(std::slice::from_ref(&parent)).unwrap();
+        assert_eq!(cleared.applied_prefix(), "");
+
+        assert!(!serde_yaml::to_string(&inheriting)
+            .unwrap()
+            .contains("prefix"));
+        assert!(serde_yaml::to_string(&clearing)
+            .unwrap()
+            .contains("prefix: ''"));
+    }
+
+    #[test]
+    fn only_whole_words_look_sensitive() {
+        for name in [
+            "KEY",
+            "API_KEY",
+            "key_id",
+            "DB_PASSWORD",
+            "GITHUB_TOKEN_2",
+            "SECRET",
+        ] {
+            assert!(looks_sensitive(name), "{}", name);
+        }
+        for name in [
+            "PRE_keyone",
+            "KEYBOARD_LAYOUT",
+            "MONKEY",
+            "TOKENIZER",
+            "HOST",
+        ] {
+            assert!(!looks_sensitive(name), "{}", name);
+        }
+    }
+
+    #[test]
+    fn references_are_only_parsed_on_request() {
+        assert_eq!(
+            Value::parse_reference("file:secrets/token".into()).unwrap(),
+            Value::Reference(Reference::File {
+                file: "secrets/token".into()
+            })
+        );
+        assert_eq!(
+            Value::parse_reference("env:HOME".into()).unwrap(),
+            Value::Reference(Reference::Env { env: "HOME".into() })
+        );
+        assert_eq!(
+            Value::parse_reference("cmd:echo a:b".into()).unwrap(),
+            Value::Reference(Reference::Cmd {
+                cmd: "echo a:b".into(),
+                timeout: None
+            })
+        );
+        assert!(Value::parse_reference("env:".into()).is_err());
+        assert!(Value::parse_reference("42".into()).is_err());
+        assert_eq!(
+            Value::parse("file:./dev.db".into()),
+            Value::String("file:./dev.db".into())
+        );
+    }
+
+    #[test]
+    fn parse_keeps_values_that_do_not_round_trip_as_strings() {
+        assert_eq!(Value::parse("007".into()), Value::String("007".into()));
+        assert_eq!(Value::parse("1e3".into()), Value::String("1e3".into()));
+        assert_eq!(Value::parse("1.50".into()), Value::String("1.50".into()));
+        assert_eq!(Value::parse("-3".into()), Value::Integer(-3));
+        assert_eq!(Value::parse("2.5".into()), Value::Float(2.5));
+        assert_eq!(Value::parse("false".into()), Value::Bool(false));
+    }
+
+    #[test]
+    fn lists_are_joined_with_the_separator() {
+        let list = value("[a, 1, true]");
+        assert_eq!(list.to_raw_string(None), "a,1,true");
+        assert_eq!(list.to_raw_string(Some(":")), "a:1:true");
+        assert_eq!(value("{a: [x]}").to_raw_string(None), r#"{"a":["x"]}"#);
+    }
 }
@@ -1,13 +1,15 @@
-use crate::components::Component;
+use crate::components::{Component, EnvVar, Missing, Value};
+use crate::error::MentalError;
+use crate::format::{RenderOptions, Section};
+use crate::interpolate;
 use crate::mapping::FileIO;
-use schemars::{schema_for, JsonSchema};
+use crate::overrides::Overrides;
+use crate::secrets::Keyring;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use serde_yaml::from_str;
-use std::error::Error;
-use std::fmt;
+use std::collections::BTreeSet;
 use std::fs::read_to_string;
-use std::fs::File;
-use std::io::Write;
 use std::path::{Path, PathBuf};
 
 /// Config Struct
@@ -16,55 +18,262 @@ use std::path::{Path, PathBuf};
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct MentalConfig {
     components: Vec<Component>,
+    /// keys to encrypt and decrypt the secrets of the components with
+    #[serde(skip)]
+    #[schemars(skip)]
+    keyring: Keyring,
+    /// folder of the config file, relative file references start here
+    #[serde(skip)]
+    #[schemars(skip)]
+    folder: PathBuf,
+    /// local values laid over the components, never written into the config
+    #[serde(skip)]
+    #[schemars(skip)]
+    overrides: Overrides,
 }
 
 impl FileIO for MentalConfig {}
 
-/// Custom error used with config
-#[derive(Debug, Clone)]
-struct ConfigError(String);
-
-impl fmt::Display for ConfigError {
-    /// Format the custom error
+impl MentalConfig {
+    /// Collect the variables of the given components
+    ///
+    /// References to other variables are resolved against the given components first and
+    /// all other components of the config second. Unknown components, required values that
+    /// are missing and values breaking their rules are an error.
     ///
-    /// * `f`: formatter
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Invalid config option: {}", self.0)
+    /// * `component_keys`: slice of component keys
+    /// * `profile`: profile whose overrides are applied
+    pub(crate) fn sections(
+        &self,
+        component_keys: &[String],
+        profile: Option<&str>,
+    ) -> Result<Vec<Section>, MentalError> {
+        let (sections, missing) = self.resolve_sections(component_keys, profile)?;
+        let missing: Vec<String> = missing.into_iter().map(|m| m.name).collect();
+        let variables: Vec<&EnvVar> = sections.iter().flat_map(|s| &s.variables).collect();
+        let broken = broken_values(&variables, &missing, false);
+        if !broken.is_empty() {
+            return Err(MentalError::Validation(format!(
+                "{} invalid values:\n  {}",
+                broken.len(),
+                broken.join("\n  ")
+            )));
+        }
+        Ok(sections)
     }
-}
 
-impl Error for ConfigError {}
-
-impl MentalConfig {
-    /// Serialize data into .env format
+    /// Collect and resolve the variables of the given components without checking them
+    ///
+    /// Returns the sections together with the required values that are missing or empty.
     ///
     /// * `component_keys`: slice of component keys
-    pub(crate) fn to_env(&self, component_keys: &[String]) -> Vec<String> {
-        let filtered_components: Vec<&Component> = self
-            .components
+    /// * `profile`: profile whose overrides are applied
+    fn resolve_sections(
+        &self,
+        component_keys: &[String],
+        profile: Option<&str>,
+    ) -> Result<(Vec<Section>, Vec<Missing>), MentalError> {
+        self.check_components(component_keys)?;
+        let mut sections: Vec<Section> = Vec::new();
+        let mut components: Vec<Component> = Vec::new();
+        for c in &self.components {
+            if component_keys.contains(&c.name) {
+                let resolved = self.resolved(c)?;
+                sections.push(Section {
+                    component: c.name.clone(),
+                    variables: resolved.variables(profile),
+                });
+                components.push(resolved);
+            }
+        }
+
+        let mut scope: Vec<EnvVar> = Vec::new();
+        for section in &sections {
+            scope.extend(section.variables.iter().cloned());
+        }
+        for c in &self.components {
+            if !component_keys.contains(&c.name) {
+                // broken components that are not rendered must not break the others
+                if let Ok(resolved) = self.resolved(c) {
+                    scope.extend(resolved.variables(profile));
+                }
+            }
+        }
+        interpolate::resolve(&mut sections, &scope, &self.keyring, &self.folder)?;
+
+        let empty: Vec<&str> = sections
+            .iter()
+            .flat_map(|s| &s.variables)
+            .filter(|v| v.required && v.raw_value().is_empty())
+            .map(|v| v.name.as_str())
+            .collect();
+        let missing: Vec<Missing> = components
             .iter()
-            .filter(|c| component_keys.contains(&c.name))
+            .flat_map(|c| c.missing(profile, &empty))
             .collect();
+        Ok((sections, missing))
+    }
 
-        let mut combined_values: Vec<String> = Vec::new();
-        for c in filtered_components {
-            let component_output = c.to_env();
-            for x in component_output {
-                combined_values.push(x);
+    /// Required values of the given components that are missing or empty
+    ///
+    /// Values are not resolved as that runs commands, values only known once they are
+    /// resolved are checked when rendered.
+    ///
+    /// * `component_keys`: slice of component keys
+    /// * `profile`: profile whose overrides are applied
+    pub(crate) fn missing_values(
+        &self,
+        component_keys: &[String],
+        profile: Option<&str>,
+    ) -> Result<Vec<Missing>, MentalError> {
+        self.check_components(component_keys)?;
+        let mut missing: Vec<Missing> = Vec::new();
+        for c in &self.components {
+            if component_keys.contains(&c.name) {
+                let resolved = self.resolved(c)?;
+                let variables = resolved.variables(profile);
+                let empty: Vec<&str> = variables
+                    .iter()
+                    .filter(|v| v.required && !is_unresolved(&v.value) && v.raw_value().is_empty())
+                    .map(|v| v.name.as_str())
+                    .collect();
+                missing.extend(resolved.missing(profile, &empty));
             }
         }
-        combined_values
+        Ok(missing)
     }
 
-    /// Dump a json schema for validation
+    /// Check the values of a component without resolving them
     ///
-    /// * `target`: filepath to save the schema into
-    pub(crate) fn create_schema(target: &PathBuf) -> std::io::Result<()> {
-        let schema = schema_for!(MentalConfig);
-        let formatted_schema = serde_json::to_string(&schema).expect("Error creating schema");
-        let mut file = File::create(target)?;
-        file.write_all(formatted_schema.as_bytes())?;
-        Ok(())
+    /// Reports required values that are not set and values breaking their rules. Secrets,
+    /// references and values referencing other variables are only checked when rendered.
+    ///
+    /// * `name`: name of the component
+    /// * `profile`: profile whose overrides are applied
+    pub(crate) fn check_values(
+        &self,
+        name: &str,
+        profile: Option<&str>,
+    ) -> Result<Vec<String>, MentalError> {
+        let resolved = self.resolved(&self.components[self.position(name)?])?;
+        let variables = resolved.variables(profile);
+        let variables: Vec<&EnvVar> = variables.iter().collect();
+        let missing: Vec<String> = resolved
+            .missing(profile, &[])
+            .into_iter()
+            .map(|m| m.name)
+            .collect();
+        Ok(broken_values(&variables, &missing, true))
+    }
+
+    /// Merge a component with the components it extends and apply the local overrides
+    ///
+    /// * `component`: component to resolve
+    fn resolved(&self, component: &Component) -> Result<Component, MentalError> {
+        let mut resolved = self.resolve_extends(component, &mut Vec::new())?;
+        for (key, value) in self.overrides.values(&component.name) {
+            resolved.override_value(key, value.clone());
+        }
+        Ok(resolved)
+    }
+
+    /// Merge a component with the components it extends
+    ///
+    /// * `component`: component to resolve
+    /// * `stack`: components currently being resolved, used to detect cycles
+    fn resolve_extends(
+        &self,
+        component: &Component,
+        stack: &mut Vec<String>,
+    ) -> Result<Component, MentalError> {
+        if component.extends.is_empty() {
+            return Ok(component.clone());
+        }
+        if stack.contains(&component.name) {
+            stack.push(component.name.clone());
+            return Err(MentalError::Validation(format!(
+                "cyclic extends {}",
+                stack.join(" -> ")
+            )));
+        }
+        stack.push(component.name.clone());
+        let mut parents: Vec<Component> = Vec::new();
+        for parent_name in &component.extends {
+            let parent = match self.components.iter().find(|c| &c.name == parent_name) {
+                Some(parent) => parent,
+                None => {
+                    return Err(MentalError::Validation(format!(
+                        "component '{}' extends unknown component '{}'",
+                        component.name, parent_name
+                    )))
+                }
+            };
+            parents.push(self.resolve_extends(parent, stack)?);
+        }
+        stack.pop();
+        component.inherit(&parents)
+    }
+
+    /// Names of the variables of a component without resolving their values
+    ///
+    /// * `name`: name of the component
+    /// * `profile`: profile whose overrides are applied
+    pub(crate) fn variable_names(
+        &self,
+        name: &str,
+        profile: Option<&str>,
+    ) -> Result<Vec<String>, MentalError> {
+        let component = match self.components.iter().find(|c| c.name == name) {
+            Some(component) => component,
+            None => {
+                return Err(MentalError::Validation(format!(
+                    "unknown component '{}'",
+                    name
+                )))
+            }
+        };
+        Ok(self
+            .resolved(component)?
+            .variables(profile)
+            .into_iter()
+            .map(|v| v.name)
+            .collect())
+    }
+
+    /// Serialize data into the given format
+    ///
+    /// * `component_keys`: slice of component keys
+    /// * `options`: format, quoting, profile and masking to render with
+    pub(crate) fn to_env(
+        &self,
+        component_keys: &[String],
+        options: &RenderOptions,
+    ) -> Result<Vec<String>, MentalError> {
+        let mut sections = self.sections(component_keys, options.profile.as_deref())?;
+        if options.mask {
+            sections.iter_mut().for_each(Section::mask);
+        }
+        options.format.render(&sections, options.quoting)
+    }
+
+    /// Collect the raw values of the given components as they are put into an environment
+    ///
+    /// * `component_keys`: slice of component keys
+    /// * `profile`: profile whose overrides are applied
+    pub(crate) fn to_env_map(
+        &self,
+        component_keys: &[String],
+        profile: Option<&str>,
+    ) -> Result<Vec<(String, String)>, MentalError> {
+        let sections = self.sections(component_keys, profile)?;
+        Ok(sections
+            .into_iter()
+            .flat_map(|s| s.variables)
+            .map(|var| {
+                let value = var.raw_value();
+                (var.name, value)
+            })
+            .collect())
     }
 
     /// List names of all defined components
@@ -76,12 +285,45 @@ impl MentalConfig {
         res
     }
 
+    /// Check that all given components exist
+    ///
+    /// * `names`: names of components
+    pub(crate) fn check_components(&self, names: &[String]) -> Result<(), MentalError> {
+        let unknown: Vec<&str> = names
+            .iter()
+            .filter(|n| !self.name_exists(n))
+            .map(String::as_str)
+            .collect();
+        match unknown.is_empty() {
+            true => Ok(()),
+            false => Err(MentalError::Validation(format!(
+                "unknown components: {}",
+                unknown.join(", ")
+            ))),
+        }
+    }
+
+    /// List names of all profiles used by any component
+    pub(crate) fn list_profiles(&self) -> Vec<String> {
+        let profiles: BTreeSet<&String> = self
+            .components
+            .iter()
+            .flat_map(|c| c.profiles.keys())
+            .collect();
+        profiles.into_iter().cloned().collect()
+    }
+
     /// Load config from file
     ///
     /// * `config_file`: path to load the config from
-    pub fn from_file(config_file: &&Path) -> Result<MentalConfig, Box<dyn Error>> {
-        let config_input = read_to_string(config_file)?;
-        let config: MentalConfig = from_str(&config_input)?;
+    pub fn from_file(config_file: &&Path) -> Result<MentalConfig, MentalError> {
+        let config_input =
+            read_to_string(config_file).map_err(|e| MentalError::io(config_file, e))?;
+        let mut config: MentalConfig =
+            from_str(&config_input).map_err(|e| MentalError::yaml(config_file, e))?;
+        config.keyring = Keyring::new(config_file);
+        config.folder = config_file.parent().unwrap_or(Path::new("")).to_path_buf();
+        config.overrides = Overrides::load(&config.folder)?;
         Ok(config)
     }
 
@@ -104,10 +346,13 @@ impl MentalConfig {
     pub fn create_component(
         mut self,
         name: String,
-        values: Vec<(String, String)>,
-    ) -> Result<Self, Box<dyn Error>> {
+        values: Vec<(String, Value)>,
+    ) -> Result<Self, MentalError> {
         if self.name_exists(&name) {
-            Err(Box::new(ConfigError("Name already exists".into())))
+            Err(MentalError::Conflict(format!(
+                "component '{}' already exists",
+                name
+            )))
         } else {
             self.components.push(Component::new(name, None, values));
             Ok(self)
@@ -123,14 +368,404 @@ impl MentalConfig {
         mut self,
         name: String,
         prefix: String,
-        values: Vec<(String, String)>,
-    ) -> Result<Self, Box<dyn Error>> {
+        values: Vec<(String, Value)>,
+    ) -> Result<Self, MentalError> {
         if self.name_exists(&name) {
-            Err(Box::new(ConfigError("Name already exists".into())))
+            Err(MentalError::Conflict(format!(
+                "component '{}' already exists",
+                name
+            )))
         } else {
             self.components
                 .push(Component::new(name, Some(prefix), values));
             Ok(self)
         }
     }
+
+    /// Components of the config in the order they are defined
+    pub(crate) fn components(&self) -> &[Component] {
+        &self.components
+    }
+
+    /// Keys the secrets of the config are encrypted with
+    pub(crate) fn keyring(&self) -> &Keyring {
+        &self.keyring
+    }
+
+    /// Check if any component holds a secret
+    pub(crate) fn has_secrets(&self) -> bool {
+        self.components.iter().any(|c| c.secrets().next().is_some())
+    }
+
+    /// Set values of a component, replacing existing values with the same name
+    ///
+    /// * `name`: name of the component
+    /// * `values`: names and values to set
+    /// * `secret`: store the values encrypted
+    /// * `reference`: store the values as references, see [`Value::parse_reference`]
+    pub(crate) fn set_values(
+        mut self,
+        name: &str,
+        values: Vec<(String, String)>,
+        secret: bool,
+        reference: bool,
+    ) -> Result<Self, MentalError> {
+        let existing = self.components.iter().flat_map(|c| c.secrets()).next();
+        let mut salt = existing.and_then(|s| s.salt());
+        if let (true, Some(existing)) = (secret, existing) {
+            // new secrets must be readable with the key of the existing ones
+            self.keyring.decrypt(existing)?;
+        }
+        let position = self.position(name)?;
+        for (key, value) in values {
+            let value = match secret {
+                true => {
+                    let encrypted = self.keyring.encrypt(&value, salt)?;
+                    salt = encrypted.salt();
+                    Value::Secret(encrypted)
+                }
+                false if reference => Value::parse_reference(value)?,
+                false => Value::parse(value),
+            };
+            self.components[position].set_value(key, value);
+        }
+        Ok(self)
+    }
+
+    /// Position of a component in the config
+    ///
+    /// * `name`: name of the component
+    fn position(&self, name: &str) -> Result<usize, MentalError> {
+        self.components
+            .iter()
+            .position(|c| c.name == name)
+            .ok_or_else(|| MentalError::Validation(format!("unknown component '{}'", name)))
+    }
+
+    /// Fail if a component with the given name exists
+    ///
+    /// * `name`: name of the component
+    fn check_free(&self, name: &str) -> Result<(), MentalError> {
+        match self.components.iter().any(|c| c.name == name) {
+            true => Err(MentalError::Conflict(format!(
+                "component '{}' already exists",
+                name
+            ))),
+            false => Ok(()),
+        }
+    }
+
+    /// Remove values of a component together with their profile overrides
+    ///
+    /// * `name`: name of the component
+    /// * `keys`: names of the values without the prefix
+    pub(crate) fn unset_values(mut self, name: &str, keys: &[String]) -> Result<Self, MentalError> {
+        let position = self.position(name)?;
+        let missing: Vec<&str> = keys
+            .iter()
+            .filter(|k| !self.components[position].unset_value(k))
+            .map(String::as_str)
+            .collect();
+        if !missing.is_empty() {
+            return Err(MentalError::Validation(format!(
+                "component '{}' has no values {}",
+                name,
+                missing.join(", ")
+            )));
+        }
+        for key in keys {
+            self.overrides.unset(name, key);
+        }
+        Ok(self)
+    }
+
+    /// Replace the prefix of a component
+    ///
+    /// * `name`: name of the component
+    /// * `prefix`: new prefix, `None` removes it, `""` clears an inherited prefix
+    pub(crate) fn set_prefix(
+        mut self,
+        name: &str,
+        prefix: Option<String>,
+    ) -> Result<Self, MentalError> {
+        let position = self.position(name)?;
+        self.components[position].set_prefix(prefix);
+        Ok(self)
+    }
+
+    /// Delete a component
+    ///
+    /// Components extended by others are kept, the extending components have to drop them
+    /// first.
+    ///
+    /// * `name`: name of the component
+    pub(crate) fn delete_component(mut self, name: &str) -> Result<Self, MentalError> {
+        let position = self.position(name)?;
+        let extending: Vec<&str> = self
+            .components
+            .iter()
+            .filter(|c| c.extends.iter().any(|e| e == name))
+            .map(|c| c.name.as_str())
+            .collect();
+        if !extending.is_empty() {
+            return Err(MentalError::Conflict(format!(
+                "component '{}' is extended by {}",
+                name,
+                extending.join(", ")
+            )));
+        }
+        self.components.remove(position);
+        self.overrides.remove_component(name);
+        Ok(self)
+    }
+
+    /// Rename a component, components extending it are updated
+    ///
+    /// * `old`: current name of the component
+    /// * `new`: new name of the component
+    pub(crate) fn rename_component(mut self, old: &str, new: &str) -> Result<Self, MentalError> {
+        let position = self.position(old)?;
+        self.check_free(new)?;
+        self.components[position].name = new.to_string();
+        for extends in self
+            .components
+            .iter_mut()
+            .flat_map(|c| c.extends.iter_mut())
+        {
+            if extends == old {
+                *extends = new.to_string();
+            }
+        }
+        self.overrides.rename_component(old, new);
+        Ok(self)
+    }
+
+    /// Copy a component under a new name
+    ///
+    /// * `source`: name of the component to copy
+    /// * `destination`: name of the copy
+    pub(crate) fn copy_component(
+        mut self,
+        source: &str,
+        destination: &str,
+    ) -> Result<Self, MentalError> {
+        let position = self.position(source)?;
+        self.check_free(destination)?;
+        let mut copy = self.components[position].clone();
+        copy.name = destination.to_string();
+        self.components.push(copy);
+        self.overrides.copy_component(source, destination);
+        Ok(self)
+    }
+
+    /// Override a value locally, see [`Overrides`]
+    ///
+    /// * `component`: name of the component
+    /// * `key`: name of the value without the prefix
+    /// * `value`: new value
+    pub(crate) fn set_override(&mut self, component: &str, key: &str, value: Value) {
+        self.overrides.set(component, key, value);
+    }
+
+    /// Local values laid over the components
+    pub(crate) fn overrides(&self) -> &Overrides {
+        &self.overrides
+    }
+
+    /// Write the local overrides if a change of the config touched them
+    pub(crate) fn save_overrides(&self) -> Result<(), MentalError> {
+        if self.overrides.is_changed() {
+            self.overrides.save(&self.folder)?;
+        }
+        Ok(())
+    }
+
+    /// Folder of the config file
+    pub(crate) fn folder(&self) -> &Path {
+        &self.folder
+    }
+
+    /// Encrypt all secrets with a new key
+    ///
+    /// Returns the config together with the number of re-encrypted secrets.
+    ///
+    /// * `keyring`: keys to encrypt the secrets with from now on
+    pub(crate) fn rotate_secrets(mut self, keyring: Keyring) -> Result<(Self, usize), MentalError> {
+        let mut salt = None;
+        let mut count = 0;
+        for component in self.components.iter_mut() {
+            for value in component.values_mut() {
+                if let Value::Secret(secret) = value {
+                    let plain = self.keyring.decrypt(secret)?;
+                    let encrypted = keyring.encrypt(&plain, salt)?;
+                    salt = encrypted.salt();
+                    *value = Value::Secret(encrypted);
+                    count += 1;
+                }
+            }
+        }
+        self.keyring = keyring;
+        Ok((self, count))
+    }
+}
+
+/// Describe required values that are not set and values breaking their rules
+///
+/// * `variables`: variables to check
+/// * `missing`: names of required variables without a value
+/// * `literal_only`: skip values that are only known once they are resolved
+fn broken_values(variables: &[&EnvVar], missing: &[String], literal_only: bool) -> Vec<String> {
+    let mut broken: Vec<String> = missing
+        .iter()
+        .map(|name| format!("{}: required value is not set", name))
+        .collect();
+    for var in variables {
+        let rules = match &var.rules {
+            Some(rules) => rules,
+            None => continue,
+        };
+        if literal_only && is_unresolved(&var.value) {
+            continue;
+        }
+        for rule in rules.check(&var.raw_value()) {
+            broken.push(format!("{}: value {}", var.name, rule));
+        }
+    }
+    broken
+}
+
+/// Check if a value is only known once it is resolved
+///
+/// * `value`: value of a variable
+fn is_unresolved(value: &Value) -> bool {
+    match value {
+        Value::Secret(_) | Value::Reference(_) => true,
+        Value::String(v) => v.contains('$'),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::secrets::Secret;
+
+    #[test]
+    fn missing_values_do_not_run_commands() {
+        let marker = std::env::temp_dir().join(format!("mental-missing-{}", std::process::id()));
+        let mut config: MentalConfig = from_str(&format!(
+            r#"
+components:
+  - name: app
+    values:
+      - name: TOKEN
+        required: true
+        value:
+          cmd: touch '{}'
+      - name: EMPTY
+        required: true
+        value: ""
+      - name: UNSET
+        required: true
+      - name: OPTIONAL
+"#,
+            marker.display()
+        ))
+        .unwrap();
+        let keys = vec!["app".to_string()];
+
+        let missing = config.missing_values(&keys, None).unwrap();
+        let names: Vec<&str> = missing.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["EMPTY", "UNSET"]);
+        assert!(!marker.exists());
+
+        config.set_override("app", "UNSET", Value::String("set".into()));
+        let missing = config.missing_values(&keys, None).unwrap();
+        let names: Vec<&str> = missing.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["EMPTY"]);
+    }
+
+    #[test]
+    fn rotating_re_encrypts_every_secret() {
+        let mut config: MentalConfig = from_str(
+            r#"
+components:
+  - name: app
+    values:
+      - name: HOST
+        value: localhost
+  - name: db
+"#,
+        )
+        .unwrap();
+        let key_file = Path::new(".mental.key");
+        config.keyring = Keyring::with_master(key_file, b"old key".to_vec());
+        let config = config
+            .set_values("app", vec![("TOKEN".into(), "abc".into())], true, false)
+            .unwrap()
+            .set_values("db", vec![("PASS".into(), "hunter2".into())], true, false)
+            .unwrap();
+
+        let (rotated, count) = config
+            .rotate_secrets(Keyring::with_master(key_file, b"new key".to_vec()))
+            .unwrap();
+        assert_eq!(count, 2);
+        let secrets: Vec<&Secret> = rotated
+            .components
+            .iter()
+            .flat_map(|c| c.secrets())
+            .collect();
+        let old = Keyring::with_master(key_file, b"old key".to_vec());
+        let new = Keyring::with_master(key_file, b"new key".to_vec());
+        assert_eq!(new.decrypt(secrets[0]).unwrap(), "abc");
+        assert_eq!(new.decrypt(secrets[1]).unwrap(), "hunter2");
+        assert!(old.decrypt(secrets[0]).is_err());
+        assert_eq!(
+            rotated.components[0].variables(None)[0].raw_value(),
+            "localhost"
+        );
+    }
+
+    #[test]
+    fn overrides_follow_changes_of_components() {
+        let mut config: MentalConfig = from_str(
+            r#"
+components:
+  - name: app
+    values:
+      - name: HOST
+        value: localhost
+      - name: PORT
+        value: 80
+"#,
+        )
+        .unwrap();
+        config.set_override("app", "HOST", Value::String("example.com".into()));
+        config.set_override("app", "PORT", Value::Integer(8080));
+        config.set_override("app", "GONE", Value::Bool(true));
+        let raw = |config: &MentalConfig, name: &str| -> Vec<String> {
+            let component = &config.components[config.position(name).unwrap()];
+            config
+                .resolved(component)
+                .unwrap()
+                .variables(None)
+                .iter()
+                .map(|v| format!("{}={}", v.name, v.raw_value()))
+                .collect()
+        };
+        assert_eq!(raw(&config, "app"), vec!["HOST=example.com", "PORT=8080"]);
+
+        let config = config.unset_values("app", &["PORT".into()]).unwrap();
+        assert_eq!(raw(&config, "app"), vec!["HOST=example.com"]);
+        assert!(config.overrides().is_changed());
+
+        let config = config.rename_component("app", "web").unwrap();
+        assert_eq!(raw(&config, "web"), vec!["HOST=example.com"]);
+        let config = config.copy_component("web", "api").unwrap();
+        assert_eq!(raw(&config, "api"), vec!["HOST=example.com"]);
+
+        let config = config.delete_component("web").unwrap();
+        assert_eq!(config.overrides().values("web").count(), 0);
+        assert_eq!(config.overrides().values("api").count(), 2);
+    }
 }
@@ -0,0 +1,307 @@
+//! Preview of the changes `apply` would make to the target files
+
+use crate::components::{looks_sensitive, MASK};
+use crate::format::OutputFormat;
+use crate::mapping::TargetFile;
+use similar::{ChangeTag, TextDiff};
+use std::collections::BTreeMap;
+
+const RED: &str = "\x1b[31m";
+const GREEN: &str = "\x1b[32m";
+const CYAN: &str = "\x1b[36m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// Number of changed variables of a target
+///
+/// * `added`: variables only present after applying
+/// * `removed`: variables only present before applying
+/// * `changed`: variables present in both with a different value
+#[derive(Debug, Default)]
+pub(crate) struct ChangeCount {
+    pub(crate) added: usize,
+    pub(crate) removed: usize,
+    pub(crate) changed: usize,
+}
+
+impl ChangeCount {
+    /// Count the variable changes of a target
+    ///
+    /// * `target`: rendered target
+    pub(crate) fn of(target: &TargetFile) -> ChangeCount {
+        let before = variables(target.current.as_deref().unwrap_or_default(), target.format);
+        let after = variables(&target.rendered, target.format);
+        let mut count = ChangeCount::default();
+        for (key, value) in &after {
+            match before.get(key) {
+                None => count.added += 1,
+                Some(old) if old != value => count.changed += 1,
+                Some(_) => {}
+            }
+        }
+        count.removed = before.keys().filter(|k| !after.contains_key(*k)).count();
+        count
+    }
+
+    /// Total number of changes
+    pub(crate) fn total(&self) -> usize {
+        self.added + self.removed + self.changed
+    }
+}
+
+/// Collect the variables of rendered content
+///
+/// * `content`: content of a rendered file
+/// * `format`: format of the file
+fn variables(content: &str, format: OutputFormat) -> BTreeMap<&str, &str> {
+    content
+        .lines()
+        .filter_map(|line| format.split_line(line))
+        .collect()
+}
+
+/// Check if a variable is sensitive
+///
+/// Variables that are not part of the config are sensitive if their name looks like a secret.
+///
+/// * `name`: name of the variable
+/// * `sensitive`: variables of the config and if they are sensitive
+fn is_sensitive(name: &str, sensitive: &BTreeMap<String, bool>) -> bool {
+    sensitive
+        .get(name)
+        .copied()
+        .unwrap_or_else(|| looks_sensitive(name))
+}
+
+/// Quote that is still open at the end of a text
+///
+/// * `text`: text to scan
+/// * `open`: quote open at the start of the text
+/// * `format`: format the text is written in
+fn open_quote(text: &str, mut open: Option<char>, format: OutputFormat) -> Option<char> {
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        let c = match (format, c) {
+            // PowerShell treats typographic single quotes like `'`
+            (OutputFormat::Powershell, '\u{2018}'..='\u{201B}') => '\'',
+            _ => c,
+        };
+        match (open, c) {
+            (None | Some('"'), '\\') if format != OutputFormat::Powershell => {
+                chars.next();
+            }
+            (Some('\''), '\\') if format == OutputFormat::Fish => {
+                chars.next();
+            }
+            (Some(quote), c) if c == quote => open = None,
+            (None, '"' | '\'') => open = Some(c),
+            _ => {}
+        }
+    }
+    open
+}
+
+/// Check if a line ends with a backslash continuing it on the next line
+///
+/// * `line`: line to check
+fn ends_escaped(line: &str) -> bool {
+    line.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1
+}
+
+/// Mask the values of sensitive variables in rendered content
+///
+/// Returns one line per line of the content. Lines continuing a sensitive value that spans
+/// multiple lines, like YAML block scalars or quoted values with line breaks, are replaced
+/// by the mask completely.
+///
+/// * `content`: rendered content
+/// * `format`: format of the content
+/// * `sensitive`: variables of the config and if they are sensitive
+pub(crate) fn mask_lines(
+    content: &str,
+    format: OutputFormat,
+    sensitive: &BTreeMap<String, bool>,
+) -> Vec<String> {
+    let lines: Vec<&str> = content.lines().collect();
+    let mut masked: Vec<String> = Vec::with_capacity(lines.len());
+    let mut in_value = false;
+    let mut open: Option<char> = None;
+    for (index, line) in lines.iter().enumerate() {
+        let indented = |l: &str| l.starts_with([' ', '\t']);
+        let continues = in_value
+            && match format {
+                OutputFormat::Yaml => {
+                    indented(line)
+                        || (line.is_empty()
+                            && lines[index..]
+                                .iter()
+                                .find(|l| !l.is_empty())
+                                .is_some_and(|l| indented(l)))
+                }
+                OutputFormat::Json => false,
+                OutputFormat::Properties => ends_escaped(lines[index - 1]),
+                _ => open.is_some(),
+            };
+        if continues {
+            open = open_quote(line, open, format);
+            masked.push(MASK.to_string());
+            continue;
+        }
+        match format.split_line(line) {
+            Some((name, value)) if is_sensitive(name, sensitive) => {
+                let start = value.as_ptr() as usize - line.as_ptr() as usize;
+                let end = start + value.len();
+                masked.push(format!("{}{}{}", &line[..start], MASK, &line[end..]));
+                in_value = true;
+                open = open_quote(value, None, format);
+            }
+            _ => {
+                masked.push(line.to_string());
+                in_value = false;
+            }
+        }
+    }
+    masked
+}
+
+/// Wrap text into a color if coloring is enabled
+///
+/// * `text`: text to color
+/// * `color`: ANSI escape sequence of the color
+/// * `colored`: if colors are enabled
+fn paint(text: &str, color: &str, colored: bool) -> String {
+    if colored {
+        format!("{}{}{}", color, text, RESET)
+    } else {
+        text.to_string()
+    }
+}
+
+/// Render a unified diff between the file on disk and the rendered file
+///
+/// Values of sensitive variables are masked unless they are revealed.
+///
+/// * `target`: rendered target
+/// * `colored`: if the output should contain ANSI colors
+/// * `reveal`: show the values of sensitive variables
+pub(crate) fn unified_diff(target: &TargetFile, colored: bool, reveal: bool) -> String {
+    let before = target.current.as_deref().unwrap_or_default();
+    let diff = TextDiff::from_lines(before, target.rendered.as_str());
+    let masked_before = mask_lines(before, target.format, &target.sensitive);
+    let masked_after = mask_lines(&target.rendered, target.format, &target.sensitive);
+    let path = target.path.display().to_string();
+    let old_name = match target.current {
+        Some(_) => path.as_str(),
+        None => "/dev/null",
+    };
+
+    let mut output = String::new();
+    output.push_str(&paint(&format!("--- {}", old_name), BOLD, colored));
+    output.push('\n');
+    output.push_str(&paint(&format!("+++ {}", path), BOLD, colored));
+    output.push('\n');
+    for hunk in diff.unified_diff().context_radius(3).iter_hunks() {
+        output.push_str(&paint(&hunk.header().to_string(), CYAN, colored));
+        output.push('\n');
+        for change in hunk.iter_changes() {
+            let masked = match (change.tag(), change.old_index(), change.new_index()) {
+                (ChangeTag::Insert, _, Some(index)) => masked_after.get(index),
+                (_, Some(index), _) => masked_before.get(index),
+                _ => None,
+            };
+            let line = match (reveal, masked) {
+                (true, _) => change.value().trim_end_matches(['\n', '\r']).to_string(),
+                (false, Some(masked)) => masked.clone(),
+                (false, None) => MASK.to_string(),
+            };
+            let formatted = match change.tag() {
+                ChangeTag::Delete => paint(&format!("-{}", line), RED, colored),
+                ChangeTag::Insert => paint(&format!("+{}", line), GREEN, colored),
+                ChangeTag::Equal => format!(" {}", line),
+            };
+            output.push_str(&formatted);
+            output.push('\n');
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::components::{EnvVar, Value};
+    use crate::format::Section;
+    use crate::quoting::Quoting;
+    use std::path::PathBuf;
+
+    /// Diff an empty file against a password and a plain variable rendered in a format
+    fn diff_of(format: OutputFormat, password: &str, current: Option<&str>) -> String {
+        let var = |name: &str, value: &str, sensitive: bool| EnvVar {
+            sensitive,
+            ..EnvVar::new(name, Value::String(value.into()))
+        };
+        let sections = [Section {
+            component: "app".into(),
+            variables: vec![
+                var("APP_PASSWORD", password, true),
+                var("APP_HOST", "localhost", false),
+            ],
+        }];
+        let rendered = format.render(&sections, Quoting::Posix).unwrap();
+        let target = TargetFile {
+            path: PathBuf::from(".env"),
+            format,
+            current: current.map(String::from),
+            rendered: format!("{}\n", rendered.join("\n")),
+            sensitive: BTreeMap::from([
+                ("APP_PASSWORD".to_string(), true),
+                ("APP_HOST".to_string(), false),
+            ]),
+        };
+        unified_diff(&target, false, false)
+    }
+
+    #[test]
+    fn multi_line_secrets_are_masked_in_every_format() {
+        let formats = [
+            OutputFormat::Dotenv,
+            OutputFormat::Shell,
+            OutputFormat::Fish,
+            OutputFormat::Powershell,
+            OutputFormat::Json,
+            OutputFormat::Yaml,
+            OutputFormat::Toml,
+            OutputFormat::Properties,
+        ];
+        for format in formats {
+            let diff = diff_of(format, "line-one\n\nhunter2 'secret'\n", None);
+            assert!(!diff.contains("line-one"), "{:?}: {}", format, diff);
+            assert!(!diff.contains("hunter2"), "{:?}: {}", format, diff);
+            assert!(diff.contains("localhost"), "{:?}: {}", format, diff);
+        }
+    }
+
+    #[test]
+    fn changed_secrets_show_as_masked_change() {
+        let diff = diff_of(
+            OutputFormat::Shell,
+            "new",
+            Some("export APP_PASSWORD='old'\nexport APP_HOST='localhost'\n"),
+        );
+        assert!(diff.contains("-export APP_PASSWORD=********"), "{}", diff);
+        assert!(diff.contains("+export APP_PASSWORD=********"), "{}", diff);
+        assert!(!diff.contains("old") && !diff.contains("new"), "{}", diff);
+    }
+
+    #[test]
+    fn secrets_in_the_file_on_disk_are_masked() {
+        let current = "APP_PASSWORD: |-\n  line-one\n\n  hunter2\nAPP_HOST: other\n";
+        let diff = diff_of(OutputFormat::Yaml, "x", Some(current));
+        assert!(
+            !diff.contains("line-one") && !diff.contains("hunter2"),
+            "{}",
+            diff
+        );
+        assert!(diff.contains("-APP_HOST: other"), "{}", diff);
+    }
+}
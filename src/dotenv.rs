@@ -0,0 +1,309 @@
+//! Parser for existing .env files
+
+use crate::error::MentalError;
+use std::error::Error;
+use std::fmt;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::Chars;
+
+/// Error raised while parsing a .env file
+///
+/// * `line`: line the error occurred in, starting at 1
+/// * `message`: description of the problem
+#[derive(Debug, Clone)]
+pub struct ParseError {
+    line: usize,
+    message: String,
+}
+
+impl fmt::Display for ParseError {
+    /// Format the parse error
+    ///
+    /// * `f`: formatter
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for ParseError {}
+
+impl ParseError {
+    /// Turn the error into an error of the cli
+    ///
+    /// * `file`: file that was parsed
+    pub(crate) fn into_error(self, file: &Path) -> MentalError {
+        MentalError::Parse {
+            file: file.to_path_buf(),
+            line: Some(self.line),
+            column: None,
+            message: self.message,
+        }
+    }
+}
+
+/// Cursor over the input keeping track of the current line
+struct Reader<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+}
+
+impl Reader<'_> {
+    /// Look at the next character without consuming it
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    /// Consume the next character
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next();
+        if c == Some('\n') {
+            self.line += 1;
+        }
+        c
+    }
+
+    /// Skip characters up to the end of the line
+    fn skip_line(&mut self) {
+        while self.peek().is_some_and(|c| c != '\n') {
+            self.next();
+        }
+    }
+
+    /// Skip spaces and tabs
+    fn skip_blanks(&mut self) {
+        while self.peek().is_some_and(|c| c == ' ' || c == '\t') {
+            self.next();
+        }
+    }
+
+    /// Create an error for the current line
+    ///
+    /// * `message`: description of the problem
+    fn error(&self, message: &str) -> ParseError {
+        ParseError {
+            line: self.line,
+            message: message.to_string(),
+        }
+    }
+
+    /// Read the name of a variable
+    fn key(&mut self) -> String {
+        let mut key = String::new();
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_alphanumeric() || c == '_' || c == '.' || c == '-') {
+                break;
+            }
+            key.push(c);
+            self.next();
+        }
+        key
+    }
+
+    /// Read a single quoted segment, the content is taken literally
+    fn single_quoted(&mut self, value: &mut String) -> Result<(), ParseError> {
+        self.next();
+        loop {
+            match self.next() {
+                None => return Err(self.error("unterminated single quoted value")),
+                Some('\'') => return Ok(()),
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    /// Read a double quoted segment resolving escape sequences
+    fn double_quoted(&mut self, value: &mut String) -> Result<(), ParseError> {
+        self.next();
+        loop {
+            match self.next() {
+                None => return Err(self.error("unterminated double quoted value")),
+                Some('"') => return Ok(()),
+                Some('\\') => match self.next() {
+                    None => return Err(self.error("unterminated double quoted value")),
+                    Some('n') => value.push('\n'),
+                    Some('r') => value.push('\r'),
+                    Some('t') => value.push('\t'),
+                    Some('\n') => {}
+                    Some(c @ ('\\' | '"' | '\'' | '$' | '`')) => value.push(c),
+                    Some(c) => {
+                        value.push('\\');
+                        value.push(c);
+                    }
+                },
+                Some(c) => value.push(c),
+            }
+        }
+    }
+
+    /// Read a value up to the end of the line
+    ///
+    /// Quoted segments may span multiple lines and are joined with adjacent segments like
+    /// in a shell. Unquoted whitespace at the end and comments after whitespace are dropped.
+    fn value(&mut self) -> Result<String, ParseError> {
+        let mut value = String::new();
+        let mut blanks = String::new();
+        while let Some(c) = self.peek() {
+            match c {
+                '\n' => break,
+                ' ' | '\t' | '\r' => {
+                    blanks.push(c);
+                    self.next();
+                    continue;
+                }
+                '#' if value.is_empty() || !blanks.is_empty() => {
+                    self.skip_line();
+                    break;
+                }
+                _ => {}
+            }
+            value.push_str(&blanks);
+            blanks.clear();
+            match c {
+                '\'' => self.single_quoted(&mut value)?,
+                '"' => self.double_quoted(&mut value)?,
+                '\\' => {
+                    self.next();
+                    match self.peek() {
+                        Some(quote @ ('\'' | '"')) => {
+                            value.push(quote);
+                            self.next();
+                        }
+                        _ => value.push('\\'),
+                    }
+                }
+                c => {
+                    value.push(c);
+                    self.next();
+                }
+            }
+        }
+        Ok(value)
+    }
+}
+
+/// Parse the content of a .env file into keys and values
+///
+/// Supports comments, `export ` prefixes, single and double quotes, escape sequences in
+/// double quotes and quoted values spanning multiple lines.
+///
+/// * `input`: content of the .env file
+pub(crate) fn parse(input: &str) -> Result<Vec<(String, String)>, ParseError> {
+    let mut reader = Reader {
+        chars: input.chars().peekable(),
+        line: 1,
+    };
+    let mut key_values: Vec<(String, String)> = Vec::new();
+    loop {
+        while reader.peek().is_some_and(|c| c.is_whitespace()) {
+            reader.next();
+        }
+        match reader.peek() {
+            None => break,
+            Some('#') => {
+                reader.skip_line();
+                continue;
+            }
+            Some(_) => {}
+        }
+
+        let mut key = reader.key();
+        if key == "export" && reader.peek().is_some_and(|c| c == ' ' || c == '\t') {
+            reader.skip_blanks();
+            key = reader.key();
+        }
+        if key.is_empty() {
+            return Err(reader.error("expected a variable name"));
+        }
+        reader.skip_blanks();
+        if reader.peek() != Some('=') {
+            return Err(reader.error(&format!("expected '=' after '{}'", key)));
+        }
+        reader.next();
+        reader.skip_blanks();
+        let value = reader.value()?;
+        key_values.push((key, value));
+    }
+    Ok(key_values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse a .env file that is expected to be valid
+    ///
+    /// * `input`: content of the .env file
+    fn pairs(input: &str) -> Vec<(String, String)> {
+        parse(input).unwrap()
+    }
+
+    /// Build the expected keys and values
+    ///
+    /// * `expected`: keys and values
+    fn expected(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+        expected
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn comments_and_blank_lines_are_skipped() {
+        let input = "# comment\n\nA=1 # trailing\n  # indented\nB=x#y\nC=\n";
+        assert_eq!(
+            pairs(input),
+            expected(&[("A", "1"), ("B", "x#y"), ("C", "")])
+        );
+    }
+
+    #[test]
+    fn export_is_dropped() {
+        assert_eq!(
+            pairs("export A=1\nexport\tB = 2\nexported=3\n"),
+            expected(&[("A", "1"), ("B", "2"), ("exported", "3")])
+        );
+    }
+
+    #[test]
+    fn single_quotes_are_literal() {
+        assert_eq!(
+            pairs(r#"A='$HOME \n "x" # no comment'"#),
+            expected(&[("A", r#"$HOME \n "x" # no comment"#)])
+        );
+    }
+
+    #[test]
+    fn double_quotes_resolve_escapes() {
+        assert_eq!(
+            pairs("A=\"a\\nb\\tc \\\"q\\\" \\\\ \\$x \\w\"\nB=\"it's\"'\"'"),
+            expected(&[("A", "a\nb\tc \"q\" \\ $x \\w"), ("B", "it's\"")])
+        );
+        assert_eq!(pairs(r#"A=\"x\""#), expected(&[("A", "\"x\"")]));
+    }
+
+    #[test]
+    fn quoted_values_span_lines() {
+        let input = "A=\"one\ntwo\"\nB='three\nfour'\nC=\"five \\\nsix\"\n";
+        assert_eq!(
+            pairs(input),
+            expected(&[("A", "one\ntwo"), ("B", "three\nfour"), ("C", "five six")])
+        );
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        assert_eq!(
+            parse("A=1\nB=\"open\n\n").unwrap_err().to_string(),
+            "line 4: unterminated double quoted value"
+        );
+        assert_eq!(
+            parse("A=1\n\nB 2\n").unwrap_err().to_string(),
+            "line 3: expected '=' after 'B'"
+        );
+        assert_eq!(
+            parse("=1\n").unwrap_err().to_string(),
+            "line 1: expected a variable name"
+        );
+    }
+}
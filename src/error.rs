@@ -0,0 +1,154 @@
+//! Errors of the cli and the exit codes they map to
+
+use inquire::InquireError;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Exit code if generated files differ from the files on disk
+pub(crate) const EXIT_DRIFT: i32 = 1;
+/// Exit code if a config, mapping or .env file could not be parsed
+pub(crate) const EXIT_PARSE: i32 = 3;
+/// Exit code if the config, a mapping or the given arguments are invalid
+pub(crate) const EXIT_VALIDATION: i32 = 4;
+/// Exit code if reading or writing a file failed
+pub(crate) const EXIT_IO: i32 = 5;
+/// Exit code if a change conflicts with existing data
+pub(crate) const EXIT_CONFLICT: i32 = 6;
+/// Exit code if the user aborted a prompt
+pub(crate) const EXIT_CANCELLED: i32 = 130;
+
+/// Error of the cli
+///
+/// Every variant maps to a stable exit code, see [`MentalError::exit_code`].
+#[derive(Debug)]
+pub enum MentalError {
+    /// A file could not be parsed
+    Parse {
+        file: PathBuf,
+        line: Option<usize>,
+        column: Option<usize>,
+        message: String,
+    },
+    /// The config, a mapping or the given arguments are invalid
+    Validation(String),
+    /// Reading or writing a file failed
+    Io { path: PathBuf, source: io::Error },
+    /// The user aborted a prompt
+    PromptCancelled,
+    /// A change conflicts with existing data, e.g. a duplicate name
+    Conflict(String),
+    /// Generated files differ from the files on disk
+    Drift(usize),
+}
+
+impl MentalError {
+    /// Create an I/O error for a path
+    ///
+    /// * `path`: file or folder the operation failed on
+    /// * `source`: underlying error
+    pub(crate) fn io(path: &Path, source: io::Error) -> MentalError {
+        MentalError::Io {
+            path: path.to_path_buf(),
+            source,
+        }
+    }
+
+    /// Create a parse error from a YAML error
+    ///
+    /// * `file`: file that failed to parse
+    /// * `error`: underlying error holding the location
+    pub(crate) fn yaml(file: &Path, error: serde_yaml::Error) -> MentalError {
+        let location = error.location();
+        MentalError::Parse {
+            file: file.to_path_buf(),
+            line: location.as_ref().map(|l| l.line()),
+            column: location.as_ref().map(|l| l.column()),
+            message: error.to_string(),
+        }
+    }
+
+    /// Exit code the error maps to
+    pub(crate) fn exit_code(&self) -> i32 {
+        match self {
+            MentalError::Parse { .. } => EXIT_PARSE,
+            MentalError::Validation(_) => EXIT_VALIDATION,
+            MentalError::Io { .. } => EXIT_IO,
+            MentalError::PromptCancelled => EXIT_CANCELLED,
+            MentalError::Conflict(_) => EXIT_CONFLICT,
+            MentalError::Drift(_) => EXIT_DRIFT,
+        }
+    }
+}
+
+impl fmt::Display for MentalError {
+    /// Format the error into a human readable message
+    ///
+    /// * `f`: formatter
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MentalError::Parse {
+                file,
+                line: Some(line),
+                column: Some(column),
+                message,
+            } => write!(
+                f,
+                "could not parse {}:{}:{}: {}",
+                file.display(),
+                line,
+                column,
+                message
+            ),
+            MentalError::Parse {
+                file,
+                line: Some(line),
+                message,
+                ..
+            } => write!(
+                f,
+                "could not parse {}:{}: {}",
+                file.display(),
+                line,
+                message
+            ),
+            MentalError::Parse { file, message, .. } => {
+                write!(f, "could not parse {}: {}", file.display(), message)
+            }
+            MentalError::Validation(message) => write!(f, "invalid: {}", message),
+            MentalError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+            MentalError::PromptCancelled => write!(f, "aborted by user"),
+            MentalError::Conflict(message) => write!(f, "conflict: {}", message),
+            MentalError::Drift(count) => write!(f, "{} targets are out of date", count),
+        }
+    }
+}
+
+impl Error for MentalError {
+    /// Underlying error if there is one
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MentalError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<InquireError> for MentalError {
+    /// Map errors of prompts, cancelling a prompt is not a failure of the cli
+    ///
+    /// * `error`: error of the prompt
+    fn from(error: InquireError) -> MentalError {
+        match error {
+            InquireError::OperationCanceled | InquireError::OperationInterrupted => {
+                MentalError::PromptCancelled
+            }
+            InquireError::NotTTY => {
+                MentalError::Validation("interactive prompt needs a terminal".into())
+            }
+            InquireError::IO(source) => MentalError::io(Path::new("<terminal>"), source),
+            error => MentalError::Validation(error.to_string()),
+        }
+    }
+}
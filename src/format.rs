@@ -0,0 +1,554 @@
+//! Output formats the variables of components can be rendered into
+
+use crate::components::{EnvVar, Value, MASK};
+use crate::error::MentalError;
+use crate::quoting::Quoting;
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// Variables of a single component
+///
+/// * `component`: name of the component
+/// * `variables`: variables with the prefix of the component applied
+#[derive(Debug)]
+pub(crate) struct Section {
+    pub(crate) component: String,
+    pub(crate) variables: Vec<EnvVar>,
+}
+
+impl Section {
+    /// Replace the values of sensitive variables with a mask
+    pub(crate) fn mask(&mut self) {
+        for var in self.variables.iter_mut().filter(|v| v.sensitive) {
+            var.value = Value::String(MASK.to_string());
+        }
+    }
+}
+
+/// Options used to render components
+///
+/// * `format`: format to render into
+/// * `quoting`: dialect used to quote string values of the dotenv format
+/// * `profile`: profile whose overrides are applied on top of the values
+/// * `mask`: replace the values of sensitive variables, used for output on the terminal
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RenderOptions {
+    pub(crate) format: OutputFormat,
+    pub(crate) quoting: Quoting,
+    pub(crate) profile: Option<String>,
+    pub(crate) mask: bool,
+}
+
+/// Format the variables are rendered into
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize, JsonSchema,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    /// `KEY="value"` lines
+    #[default]
+    Dotenv,
+    /// `export KEY='value'` lines for POSIX shells
+    Shell,
+    /// `set -gx KEY 'value'` lines for fish
+    Fish,
+    /// `$env:KEY = 'value'` lines for PowerShell
+    Powershell,
+    /// single JSON object
+    Json,
+    /// YAML mapping
+    Yaml,
+    /// TOML table
+    Toml,
+    /// Java `.properties`
+    Properties,
+}
+
+impl OutputFormat {
+    /// Name of the file written into a target folder
+    pub(crate) fn default_file_name(&self) -> &'static str {
+        match self {
+            OutputFormat::Dotenv => ".env",
+            OutputFormat::Shell => ".env.sh",
+            OutputFormat::Fish => ".env.fish",
+            OutputFormat::Powershell => ".env.ps1",
+            OutputFormat::Json => ".env.json",
+            OutputFormat::Yaml => ".env.yaml",
+            OutputFormat::Toml => ".env.toml",
+            OutputFormat::Properties => ".env.properties",
+        }
+    }
+
+    /// Guess the format from the extension of a file name
+    ///
+    /// * `file`: name of the file
+    pub(crate) fn from_file_name(file: &Path) -> Option<OutputFormat> {
+        match file.extension()?.to_str()? {
+            "sh" => Some(OutputFormat::Shell),
+            "fish" => Some(OutputFormat::Fish),
+            "ps1" => Some(OutputFormat::Powershell),
+            "json" => Some(OutputFormat::Json),
+            "yaml" | "yml" => Some(OutputFormat::Yaml),
+            "toml" => Some(OutputFormat::Toml),
+            "properties" => Some(OutputFormat::Properties),
+            _ => None,
+        }
+    }
+
+    /// Check if the format has `#` line comments
+    ///
+    /// Only formats with comments can hold a managed block next to other content.
+    pub(crate) fn supports_comments(&self) -> bool {
+        !matches!(self, OutputFormat::Json)
+    }
+
+    /// Render the variables into lines of the format
+    ///
+    /// * `sections`: variables grouped by component
+    /// * `quoting`: dialect used to quote string values of the dotenv format
+    pub(crate) fn render(
+        &self,
+        sections: &[Section],
+        quoting: Quoting,
+    ) -> Result<Vec<String>, MentalError> {
+        match self {
+            OutputFormat::Json => {
+                let mut object = serde_json::Map::new();
+                for var in sections.iter().flat_map(|s| &s.variables) {
+                    let value = serde_json::to_value(&var.value).map_err(serialization_error)?;
+                    object.insert(var.name.clone(), value);
+                }
+                let rendered =
+                    serde_json::to_string_pretty(&object).map_err(serialization_error)?;
+                Ok(rendered.lines().map(String::from).collect())
+            }
+            OutputFormat::Yaml => {
+                let mut lines: Vec<String> = Vec::new();
+                for section in sections {
+                    lines.push(format!("# component {}", section.component));
+                    if section.variables.is_empty() {
+                        continue;
+                    }
+                    let mut mapping = serde_yaml::Mapping::new();
+                    for var in &section.variables {
+                        let value =
+                            serde_yaml::to_value(&var.value).map_err(serialization_error)?;
+                        mapping.insert(var.name.clone().into(), value);
+                    }
+                    lines.extend(
+                        serde_yaml::to_string(&mapping)
+                            .map_err(serialization_error)?
+                            .lines()
+                            .map(String::from),
+                    );
+                }
+                Ok(lines)
+            }
+            _ => {
+                let mut lines: Vec<String> = Vec::new();
+                for section in sections {
+                    lines.push(format!("# component {}", section.component));
+                    for var in &section.variables {
+                        lines.push(self.render_line(var, quoting)?);
+                    }
+                }
+                Ok(lines)
+            }
+        }
+    }
+
+    /// Render a single variable of a line based format
+    ///
+    /// * `var`: variable to render
+    /// * `quoting`: dialect used to quote string values of the dotenv format
+    pub(crate) fn render_line(
+        &self,
+        var: &EnvVar,
+        quoting: Quoting,
+    ) -> Result<String, MentalError> {
+        let name = &var.name;
+        let value = match &var.value {
+            Value::Secret(_) | Value::Reference(_) => {
+                return Err(MentalError::Validation(format!(
+                    "value of '{}' was not resolved",
+                    name
+                )))
+            }
+            v if v.is_bare() => {
+                let v = var.raw_value();
+                return Ok(match self {
+                    OutputFormat::Shell => format!("export {}={}", name, v),
+                    OutputFormat::Fish => format!("set -gx {} {}", name, v),
+                    // a bare boolean would be run as a command
+                    OutputFormat::Powershell if matches!(var.value, Value::Bool(_)) => {
+                        format!("$env:{} = '{}'", name, v)
+                    }
+                    OutputFormat::Powershell => format!("$env:{} = {}", name, v),
+                    OutputFormat::Toml => format!("{} = {}", toml_key(name), v),
+                    OutputFormat::Properties => format!("{}={}", escape_properties(name, true), v),
+                    _ => format!("{}={}", name, v),
+                });
+            }
+            _ => var.raw_value(),
+        };
+        let value = value.as_str();
+        Ok(match self {
+            OutputFormat::Shell => {
+                format!("export {}={}", name, Quoting::Posix.quote(value).unwrap())
+            }
+            OutputFormat::Fish => format!("set -gx {} '{}'", name, escape_fish(value)),
+            OutputFormat::Powershell => format!("$env:{} = '{}'", name, escape_powershell(value)),
+            OutputFormat::Toml => format!(r#"{} = "{}""#, toml_key(name), escape_toml(value)),
+            OutputFormat::Properties => format!(
+                "{}={}",
+                escape_properties(name, true),
+                escape_properties(value, false)
+            ),
+            _ => match quoting.quote(value) {
+                Some(quoted) => format!("{}={}", name, quoted),
+                None => {
+                    return Err(MentalError::Validation(format!(
+                        "value of '{}' can not be quoted as {:?}",
+                        name, quoting
+                    )))
+                }
+            },
+        })
+    }
+
+    /// Split a rendered line into the name and the value of the variable
+    ///
+    /// Returns `None` for lines that do not hold a variable.
+    ///
+    /// * `line`: line rendered in this format
+    pub(crate) fn split_line<'a>(&self, line: &'a str) -> Option<(&'a str, &'a str)> {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with('#') {
+            return None;
+        }
+        let (name, value) = match self {
+            OutputFormat::Dotenv => {
+                let trimmed = trimmed.strip_prefix("export ").unwrap_or(trimmed);
+                trimmed.split_once('=')?
+            }
+            OutputFormat::Shell => trimmed.strip_prefix("export ")?.split_once('=')?,
+            OutputFormat::Fish => trimmed.strip_prefix("set -gx ")?.split_once(' ')?,
+            OutputFormat::Powershell => trimmed.strip_prefix("$env:")?.split_once(" = ")?,
+            OutputFormat::Json => {
+                let (name, value) = trimmed.split_once(": ")?;
+                (name.trim_matches('"'), value.trim_end_matches(','))
+            }
+            OutputFormat::Yaml => trimmed.split_once(": ")?,
+            OutputFormat::Toml => trimmed.split_once(" = ")?,
+            OutputFormat::Properties => {
+                if trimmed.starts_with('!') {
+                    return None;
+                }
+                trimmed.split_once('=')?
+            }
+        };
+        Some((name.trim(), value))
+    }
+}
+
+/// Wrap an error raised while serializing values
+///
+/// * `error`: error of the serializer
+fn serialization_error(error: impl fmt::Display) -> MentalError {
+    MentalError::Validation(format!("could not serialize value: {}", error))
+}
+
+/// Escape a value inside fish single quotes
+///
+/// * `value`: raw value
+fn escape_fish(value: &str) -> String {
+    value.replace('\\', r"\\").replace('\'', r"\'")
+}
+
+/// Escape a value inside PowerShell single quotes
+///
+/// * `value`: raw value
+fn escape_powershell(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        // PowerShell treats typographic single quotes like `'`
+        if matches!(c, '\'' | '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}') {
+            escaped.push(c);
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escape a value inside a TOML basic string
+///
+/// * `value`: raw value
+fn escape_toml(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str(r"\\"),
+            '"' => escaped.push_str(r#"\""#),
+            '\n' => escaped.push_str(r"\n"),
+            '\r' => escaped.push_str(r"\r"),
+            '\t' => escaped.push_str(r"\t"),
+            c if c.is_control() => escaped.push_str(&format!(r"\u{:04X}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Format a TOML key, quoting it if it is not a bare key
+///
+/// * `name`: name of the variable
+fn toml_key(name: &str) -> String {
+    let is_bare = !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+    match is_bare {
+        true => name.to_string(),
+        false => format!(r#""{}""#, escape_toml(name)),
+    }
+}
+
+/// Escape a key or value of a Java `.properties` file
+///
+/// Non ASCII characters are written as `\uXXXX` since `.properties` files are read as
+/// ISO-8859-1 by default.
+///
+/// * `value`: raw key or value
+/// * `is_key`: if all whitespace has to be escaped
+fn escape_properties(value: &str, is_key: bool) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for (i, c) in value.chars().enumerate() {
+        match c {
+            '\\' => escaped.push_str(r"\\"),
+            '\n' => escaped.push_str(r"\n"),
+            '\r' => escaped.push_str(r"\r"),
+            '\t' => escaped.push_str(r"\t"),
+            '\x0C' => escaped.push_str(r"\f"),
+            '=' | ':' | '#' | '!' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            ' ' if is_key || i == 0 => escaped.push_str(r"\ "),
+            c if !c.is_ascii() || c.is_ascii_control() => {
+                let mut buffer = [0u16; 2];
+                for unit in c.encode_utf16(&mut buffer) {
+                    escaped.push_str(&format!(r"\u{:04X}", unit));
+                }
+            }
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Section holding a string, a number and a boolean
+    fn sections() -> Vec<Section> {
+        vec![Section {
+            component: "app".into(),
+            variables: vec![
+                EnvVar::new("APP_NAME", Value::String("it's \"x\"".into())),
+                EnvVar::new("APP_PORT", Value::Integer(8080)),
+                EnvVar::new("APP_DEBUG", Value::Bool(true)),
+            ],
+        }]
+    }
+
+    #[test]
+    fn variables_are_rendered_in_every_format() {
+        let expected = [
+            (
+                OutputFormat::Dotenv,
+                vec![
+                    "# component app",
+                    r#"APP_NAME="it's \"x\"""#,
+                    "APP_PORT=8080",
+                    "APP_DEBUG=true",
+                ],
+            ),
+            (
+                OutputFormat::Shell,
+                vec![
+                    "# component app",
+                    r#"export APP_NAME='it'\''s "x"'"#,
+                    "export APP_PORT=8080",
+                    "export APP_DEBUG=true",
+                ],
+            ),
+            (
+                OutputFormat::Fish,
+                vec![
+                    "# component app",
+                    r#"set -gx APP_NAME 'it\'s "x"'"#,
+                    "set -gx APP_PORT 8080",
+                    "set -gx APP_DEBUG true",
+                ],
+            ),
+            (
+                OutputFormat::Powershell,
+                vec![
+                    "# component app",
+                    r#"$env:APP_NAME = 'it''s "x"'"#,
+                    "$env:APP_PORT = 8080",
+                    "$env:APP_DEBUG = 'true'",
+                ],
+            ),
+            (
+                OutputFormat::Json,
+                vec![
+                    "{",
+                    r#"  "APP_DEBUG": true,"#,
+                    r#"  "APP_NAME": "it's \"x\"","#,
+                    r#"  "APP_PORT": 8080"#,
+                    "}",
+                ],
+            ),
+            (
+                OutputFormat::Yaml,
+                vec![
+                    "# component app",
+                    r#"APP_NAME: it's "x""#,
+                    "APP_PORT: 8080",
+                    "APP_DEBUG: true",
+                ],
+            ),
+            (
+                OutputFormat::Toml,
+                vec![
+                    "# component app",
+                    r#"APP_NAME = "it's \"x\"""#,
+                    "APP_PORT = 8080",
+                    "APP_DEBUG = true",
+                ],
+            ),
+            (
+                OutputFormat::Properties,
+                vec![
+                    "# component app",
+                    r#"APP_NAME=it's "x""#,
+                    "APP_PORT=8080",
+                    "APP_DEBUG=true",
+                ],
+            ),
+        ];
+        for (format, lines) in expected {
+            assert_eq!(
+                format.render(&sections(), Quoting::Compose).unwrap(),
+                lines,
+                "{:?}",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn rendered_lines_split_into_name_and_value() {
+        for format in OutputFormat::value_variants() {
+            let rendered = format.render(&sections(), Quoting::Compose).unwrap();
+            let mut names: Vec<&str> = rendered
+                .iter()
+                .filter_map(|l| format.split_line(l))
+                .map(|(name, _)| name)
+                .collect();
+            names.sort();
+            assert_eq!(
+                names,
+                vec!["APP_DEBUG", "APP_NAME", "APP_PORT"],
+                "{:?}",
+                format
+            );
+        }
+    }
+
+    #[test]
+    fn values_are_escaped_for_the_format() {
+        let newline = EnvVar::new("TEXT", Value::String("a\nb\\c #d".into()));
+        assert_eq!(
+            OutputFormat::Toml
+                .render_line(&newline, Quoting::Compose)
+                .unwrap(),
+            r#"TEXT = "a\nb\\c #d""#
+        );
+        assert_eq!(
+            OutputFormat::Properties
+                .render_line(&newline, Quoting::Compose)
+                .unwrap(),
+            r"TEXT=a\nb\\c \#d"
+        );
+        let special = EnvVar::new("my key", Value::String(" ünï:".into()));
+        assert_eq!(
+            OutputFormat::Properties
+                .render_line(&special, Quoting::Compose)
+                .unwrap(),
+            r"my\ key=\ \u00FCn\u00EF\:"
+        );
+        assert_eq!(
+            OutputFormat::Toml
+                .render_line(&special, Quoting::Compose)
+                .unwrap(),
+            r#""my key" = " ünï:""#
+        );
+        let typographic = EnvVar::new("QUOTE", Value::String("it\u{2019}s".into()));
+        assert_eq!(
+            OutputFormat::Powershell
+                .render_line(&typographic, Quoting::Compose)
+                .unwrap(),
+            "$env:QUOTE = 'it\u{2019}\u{2019}s'"
+        );
+    }
+
+    #[test]
+    fn sensitive_values_are_masked() {
+        let mut section = Section {
+            component: "db".into(),
+            variables: vec![
+                EnvVar {
+                    sensitive: true,
+                    ..EnvVar::new("DB_PASSWORD", Value::String("hunter2".into()))
+                },
+                EnvVar::new("DB_PORT", Value::Integer(5432)),
+            ],
+        };
+        section.mask();
+        assert_eq!(section.variables[0].value, Value::String(MASK.into()));
+        assert_eq!(section.variables[1].value, Value::Integer(5432));
+    }
+
+    #[test]
+    fn unresolved_values_are_not_rendered() {
+        let reference: Value = serde_yaml::from_str("env: HOME").unwrap();
+        let error = OutputFormat::Dotenv
+            .render_line(&EnvVar::new("HOME_DIR", reference), Quoting::Compose)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid: value of 'HOME_DIR' was not resolved"
+        );
+    }
+
+    #[test]
+    fn formats_are_guessed_from_the_extension() {
+        for format in OutputFormat::value_variants() {
+            let guessed = OutputFormat::from_file_name(Path::new(format.default_file_name()));
+            match format {
+                OutputFormat::Dotenv => assert_eq!(guessed, None),
+                format => assert_eq!(guessed, Some(*format)),
+            }
+        }
+        assert_eq!(
+            OutputFormat::from_file_name(Path::new("config/app.yml")),
+            Some(OutputFormat::Yaml)
+        );
+    }
+}
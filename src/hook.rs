@@ -0,0 +1,297 @@
+//! Shell integration loading the variables of a target when entering its folder
+
+use crate::components::{is_posix_name, EnvVar, Value};
+use crate::error::MentalError;
+use crate::format::OutputFormat;
+use crate::mapping::MentalMapping;
+use crate::quoting::Quoting;
+use clap::ValueEnum;
+use std::env;
+use std::path::{Path, PathBuf};
+
+/// Variable holding the names of the variables exported by the hook
+const LOADED_VARIABLE: &str = "MENTAL_LOADED";
+/// Variable holding the folder the hook last ran in
+const DIR_VARIABLE: &str = "MENTAL_DIR";
+
+/// Shell the hook is installed into
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+impl Shell {
+    /// Quote a single word of a command line
+    ///
+    /// * `word`: raw word
+    fn quote(&self, word: &str) -> String {
+        match self {
+            Shell::Fish => format!("'{}'", word.replace('\\', r"\\").replace('\'', r"\'")),
+            _ => Quoting::Posix.quote(word).unwrap(),
+        }
+    }
+
+    /// Format the variables are exported in
+    fn format(&self) -> OutputFormat {
+        match self {
+            Shell::Fish => OutputFormat::Fish,
+            _ => OutputFormat::Shell,
+        }
+    }
+
+    /// Render a statement setting a variable
+    ///
+    /// * `name`: name of the variable
+    /// * `value`: value of the variable
+    fn export(&self, name: &str, value: &str) -> Result<String, MentalError> {
+        let var = EnvVar::new(name, Value::String(value.to_string()));
+        self.format().render_line(&var, Quoting::default())
+    }
+
+    /// Render a statement removing a variable
+    ///
+    /// * `name`: name of the variable
+    fn unset(&self, name: &str) -> String {
+        match self {
+            Shell::Fish => format!("set -e {}", name),
+            _ => format!("unset {}", name),
+        }
+    }
+
+    /// Shell code calling `hook-env` whenever the working directory changes
+    ///
+    /// * `command`: quoted words of the `hook-env` invocation
+    pub(crate) fn snippet(&self, command: &[String]) -> String {
+        let command = command.join(" ");
+        match self {
+            Shell::Bash => format!(
+                r#"_mental_hook() {{
+  local previous_exit_status=$?
+  eval "$({command})"
+  return $previous_exit_status
+}}
+if [[ ";${{PROMPT_COMMAND[*]:-}};" != *";_mental_hook;"* ]]; then
+  PROMPT_COMMAND="_mental_hook${{PROMPT_COMMAND:+;$PROMPT_COMMAND}}"
+fi
+"#
+            ),
+            Shell::Zsh => format!(
+                r#"_mental_hook() {{
+  eval "$({command})"
+}}
+typeset -ag chpwd_functions
+if (( ! ${{chpwd_functions[(I)_mental_hook]}} )); then
+  chpwd_functions=(_mental_hook $chpwd_functions)
+fi
+_mental_hook
+"#
+            ),
+            Shell::Fish => format!(
+                r#"function _mental_hook --on-variable PWD
+    {command} | source
+end
+_mental_hook
+"#
+            ),
+        }
+    }
+
+    /// Quote the words of a `hook-env` invocation for the snippet
+    ///
+    /// * `words`: program followed by its arguments
+    pub(crate) fn command(&self, words: &[String]) -> Vec<String> {
+        words.iter().map(|w| self.quote(w)).collect()
+    }
+}
+
+/// Find the target a folder belongs to
+///
+/// Targets are resolved relative to the folder of their mapping file. If targets are
+/// nested the deepest target containing the folder wins.
+///
+/// * `mapping_files`: mapping files to search
+/// * `dir`: canonical folder to look up
+pub(crate) fn find_target(
+    mapping_files: &[PathBuf],
+    dir: &Path,
+) -> Result<Option<(PathBuf, Vec<String>)>, MentalError> {
+    let mut found: Option<(PathBuf, Vec<String>)> = None;
+    for mapping_file in mapping_files {
+        let mapping = MentalMapping::from_file(&mapping_file.as_path())?;
+        for target in mapping.list_targets() {
+            let folder = match mapping.target_folder(&target).canonicalize() {
+                Ok(folder) => folder,
+                Err(_) => continue,
+            };
+            let is_deeper = found
+                .as_ref()
+                .is_none_or(|(current, _)| folder.starts_with(current) && folder != *current);
+            if dir.starts_with(&folder) && is_deeper {
+                let components = mapping.components_for(&target);
+                found = Some((folder, components));
+            }
+        }
+    }
+    Ok(found)
+}
+
+/// Render the shell code switching from the previously loaded variables to new ones
+///
+/// Returns the code and a notice listing added (`+`), changed (`~`) and removed (`-`)
+/// variables. Names that are no valid shell variables are skipped.
+///
+/// * `shell`: shell the code is evaluated in
+/// * `variables`: variables of the current target, empty if there is none
+/// * `dir`: folder the hook runs in
+pub(crate) fn env_script(
+    shell: Shell,
+    variables: &[(String, String)],
+    dir: &Path,
+) -> Result<(Vec<String>, Vec<String>), MentalError> {
+    switch_script(shell, variables, dir, |name| env::var(name).ok())
+}
+
+/// Render the shell code of [`env_script`] against a given environment
+///
+/// * `shell`: shell the code is evaluated in
+/// * `variables`: variables of the current target, empty if there is none
+/// * `dir`: folder the hook runs in
+/// * `current`: value of a variable in the environment the code is evaluated in
+fn switch_script(
+    shell: Shell,
+    variables: &[(String, String)],
+    dir: &Path,
+    current: impl Fn(&str) -> Option<String>,
+) -> Result<(Vec<String>, Vec<String>), MentalError> {
+    let previous: Vec<String> = current(LOADED_VARIABLE)
+        .unwrap_or_default()
+        .split(':')
+        .filter(|n| !n.is_empty())
+        .map(String::from)
+        .collect();
+    let mut lines: Vec<String> = Vec::new();
+    let mut changes: Vec<String> = Vec::new();
+    let mut loaded: Vec<&str> = Vec::new();
+
+    for name in &previous {
+        if !variables.iter().any(|(n, _)| n == name) {
+            lines.push(shell.unset(name));
+            changes.push(format!("-{}", name));
+        }
+    }
+    for (name, value) in variables {
+        if !is_posix_name(name) || loaded.contains(&name.as_str()) {
+            continue;
+        }
+        match current(name) {
+            Some(current) if current == *value => {}
+            Some(_) => changes.push(format!("~{}", name)),
+            None => changes.push(format!("+{}", name)),
+        }
+        lines.push(shell.export(name, value)?);
+        loaded.push(name);
+    }
+
+    let dir = dir.to_string_lossy();
+    match loaded.is_empty() {
+        true => lines.push(shell.unset(LOADED_VARIABLE)),
+        false => lines.push(shell.export(LOADED_VARIABLE, &loaded.join(":"))?),
+    }
+    lines.push(shell.export(DIR_VARIABLE, &dir)?);
+    Ok((lines, changes))
+}
+
+/// Check if the hook already ran in a folder
+///
+/// * `dir`: canonical folder the hook runs in
+pub(crate) fn is_current(dir: &Path) -> bool {
+    env::var_os(DIR_VARIABLE).is_some_and(|d| Path::new(&d) == dir)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    /// Variables as returned by the components
+    ///
+    /// * `variables`: names and values
+    fn variables(variables: &[(&str, &str)]) -> Vec<(String, String)> {
+        variables
+            .iter()
+            .map(|(n, v)| (n.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn variables_are_switched() {
+        let environment = |name: &str| match name {
+            "MENTAL_LOADED" => Some("OLD:KEPT:CHANGED".to_string()),
+            "KEPT" => Some("1".to_string()),
+            "CHANGED" => Some("before".to_string()),
+            _ => None,
+        };
+        let variables = variables(&[
+            ("KEPT", "1"),
+            ("CHANGED", "after"),
+            ("NEW", "it's"),
+            ("not-posix", "x"),
+            ("NEW", "twice"),
+        ]);
+        let (lines, changes) =
+            switch_script(Shell::Bash, &variables, Path::new("/app"), environment).unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                "unset OLD",
+                "export KEPT='1'",
+                "export CHANGED='after'",
+                r#"export NEW='it'\''s'"#,
+                "export MENTAL_LOADED='KEPT:CHANGED:NEW'",
+                "export MENTAL_DIR='/app'",
+            ]
+        );
+        assert_eq!(changes, vec!["-OLD", "~CHANGED", "+NEW"]);
+    }
+
+    #[test]
+    fn leaving_a_target_unsets_its_variables() {
+        let environment = |name: &str| (name == "MENTAL_LOADED").then(|| "A".to_string());
+        let (lines, changes) =
+            switch_script(Shell::Fish, &[], Path::new("/tmp"), environment).unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                "set -e A",
+                "set -e MENTAL_LOADED",
+                "set -gx MENTAL_DIR '/tmp'"
+            ]
+        );
+        assert_eq!(changes, vec!["-A"]);
+    }
+
+    #[test]
+    fn the_deepest_target_relative_to_the_mapping_file_wins() {
+        let folder = env::temp_dir().join(format!("mental-hook-{}", std::process::id()));
+        fs::create_dir_all(folder.join("app/api/src")).unwrap();
+        let folder = folder.canonicalize().unwrap();
+        let mapping_file = folder.join("m.map");
+        fs::write(
+            &mapping_file,
+            "mappings: [{path: app, components: [web]}, {path: app/api, components: [db]}]",
+        )
+        .unwrap();
+
+        let files = [mapping_file];
+        let found = find_target(&files, &folder.join("app/api/src")).unwrap();
+        let outside = find_target(&files, &folder).unwrap();
+        fs::remove_dir_all(&folder).unwrap();
+        assert_eq!(
+            found,
+            Some((folder.join("app/api"), vec!["db".to_string()]))
+        );
+        assert_eq!(outside, None);
+    }
+}
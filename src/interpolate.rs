@@ -0,0 +1,288 @@
+//! Interpolation of `${NAME}` references between variables
+
+use crate::components::{EnvVar, Value};
+use crate::error::MentalError;
+use crate::format::Section;
+use crate::secrets::Keyring;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Resolves references against a scope of variables
+///
+/// * `scope`: variables references can point to
+/// * `resolved`: cache of already expanded variables and if they are sensitive
+/// * `stack`: variables currently being expanded, used to detect cycles
+/// * `tainted`: if the current expansion used a sensitive variable
+/// * `keyring`: keys to decrypt secrets with
+/// * `base`: folder relative file references start at
+struct Resolver<'a> {
+    keyring: &'a Keyring,
+    base: &'a Path,
+    scope: HashMap<&'a str, &'a EnvVar>,
+    resolved: HashMap<String, (String, bool)>,
+    stack: Vec<String>,
+    tainted: bool,
+}
+
+impl Resolver<'_> {
+    /// Expand the value of a variable from the scope
+    ///
+    /// * `name`: name of the variable including the prefix
+    fn variable(&mut self, name: &str) -> Result<String, MentalError> {
+        if let Some((value, sensitive)) = self.resolved.get(name) {
+            self.tainted |= sensitive;
+            return Ok(value.clone());
+        }
+        if let Some(start) = self.stack.iter().position(|n| n == name) {
+            let mut cycle = self.stack[start..].to_vec();
+            cycle.push(name.to_string());
+            return Err(MentalError::Validation(format!(
+                "cyclic reference {}",
+                cycle.join(" -> ")
+            )));
+        }
+        let var = match self.scope.get(name) {
+            Some(var) => *var,
+            None => {
+                let referenced_by = self.stack.last().map(String::as_str).unwrap_or_default();
+                return Err(MentalError::Validation(format!(
+                    "unresolved reference '${{{}}}' in '{}'",
+                    name, referenced_by
+                )));
+            }
+        };
+        self.stack.push(name.to_string());
+        let outer = std::mem::replace(&mut self.tainted, var.sensitive);
+        let expanded = match &var.value {
+            Value::String(v) => self.expand(v),
+            _ => self.literal(var),
+        };
+        self.stack.pop();
+        let sensitive = self.tainted;
+        self.tainted |= outer;
+        let expanded = expanded?;
+        self.resolved
+            .insert(name.to_string(), (expanded.clone(), sensitive));
+        Ok(expanded)
+    }
+
+    /// Read a value that is not expanded, decrypting secrets and reading references
+    ///
+    /// * `var`: variable to read
+    fn literal(&self, var: &EnvVar) -> Result<String, MentalError> {
+        match &var.value {
+            Value::Secret(v) => self.keyring.decrypt(v),
+            Value::Reference(v) => v.resolve(&var.name, self.base),
+            _ => Ok(var.raw_value()),
+        }
+    }
+
+    /// Replace the references in a template
+    ///
+    /// `$${` is written as a literal `${`.
+    ///
+    /// * `template`: value containing references
+    fn expand(&mut self, template: &str) -> Result<String, MentalError> {
+        let mut expanded = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(position) = rest.find('$') {
+            expanded.push_str(&rest[..position]);
+            let reference = &rest[position..];
+            if let Some(after) = reference.strip_prefix("$${") {
+                expanded.push_str("${");
+                rest = after;
+            } else if reference.starts_with("${") {
+                let end = match reference.find('}') {
+                    Some(end) => end,
+                    None => {
+                        return Err(MentalError::Validation(format!(
+                            "unterminated reference in '{}'",
+                            template
+                        )))
+                    }
+                };
+                expanded.push_str(&self.variable(&reference[2..end])?);
+                rest = &reference[end + 1..];
+            } else {
+                expanded.push('$');
+                rest = &reference[1..];
+            }
+        }
+        expanded.push_str(rest);
+        Ok(expanded)
+    }
+}
+
+/// Resolve the `${NAME}` references in the values of the sections and decrypt secrets
+///
+/// References use the full name of a variable including the prefix of its component. If a
+/// name is defined more than once the first variable in the scope is used. Secrets and
+/// values of files, the environment or commands are taken literally and only read if they
+/// are rendered or referenced. Variables referencing sensitive variables become sensitive.
+///
+/// * `sections`: sections whose values are resolved
+/// * `scope`: variables references can point to
+/// * `keyring`: keys to decrypt secrets with
+/// * `base`: folder relative file references start at
+pub(crate) fn resolve(
+    sections: &mut [Section],
+    scope: &[EnvVar],
+    keyring: &Keyring,
+    base: &Path,
+) -> Result<(), MentalError> {
+    let mut resolver = Resolver {
+        keyring,
+        base,
+        scope: HashMap::new(),
+        resolved: HashMap::new(),
+        stack: Vec::new(),
+        tainted: false,
+    };
+    for var in scope {
+        resolver.scope.entry(var.name.as_str()).or_insert(var);
+    }
+    for var in sections.iter_mut().flat_map(|s| s.variables.iter_mut()) {
+        match &var.value {
+            Value::String(value) if value.contains('$') => {
+                resolver.stack.push(var.name.clone());
+                resolver.tainted = false;
+                let expanded = resolver.expand(value);
+                resolver.stack.pop();
+                var.value = Value::String(expanded?);
+                var.sensitive |= resolver.tainted;
+            }
+            Value::Secret(_) | Value::Reference(_) => {
+                // share the value with references to it, commands only run once
+                let value = match resolver.scope.get(var.name.as_str()) {
+                    Some(scoped) if scoped.value == var.value => resolver.variable(&var.name)?,
+                    _ => resolver.literal(var)?,
+                };
+                var.value = Value::String(value);
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::references::Reference;
+
+    /// Variable that is not sensitive
+    ///
+    /// * `name`: name of the variable
+    /// * `value`: value of the variable
+    fn var(name: &str, value: &str) -> EnvVar {
+        EnvVar::new(name, Value::String(value.into()))
+    }
+
+    /// Resolve the variables of a single section against themselves and extra variables
+    ///
+    /// * `variables`: variables of the section
+    /// * `extra`: variables of other components
+    fn resolved(variables: Vec<EnvVar>, extra: Vec<EnvVar>) -> Result<Vec<EnvVar>, MentalError> {
+        let mut scope = variables.clone();
+        scope.extend(extra);
+        let mut sections = [Section {
+            component: "app".into(),
+            variables,
+        }];
+        resolve(&mut sections, &scope, &Keyring::default(), Path::new("."))?;
+        let [section] = sections;
+        Ok(section.variables)
+    }
+
+    /// Raw values of variables
+    ///
+    /// * `variables`: resolved variables
+    fn values(variables: &[EnvVar]) -> Vec<String> {
+        variables.iter().map(EnvVar::raw_value).collect()
+    }
+
+    #[test]
+    fn references_are_expanded_recursively() {
+        let variables = resolved(
+            vec![
+                var("URL", "http://${HOST}:${PORT}/"),
+                var("HOST", "${DOMAIN}"),
+                EnvVar::new("PORT", Value::Integer(8080)),
+            ],
+            vec![var("DOMAIN", "example.com")],
+        )
+        .unwrap();
+        assert_eq!(
+            values(&variables),
+            vec!["http://example.com:8080/", "example.com", "8080"]
+        );
+    }
+
+    #[test]
+    fn dollars_without_braces_stay() {
+        let variables = resolved(
+            vec![
+                var("PRICE", "$5 and $${LITERAL} and $$"),
+                var("LONE", "cost: $"),
+            ],
+            vec![],
+        )
+        .unwrap();
+        assert_eq!(
+            values(&variables),
+            vec!["$5 and ${LITERAL} and $$", "cost: $"]
+        );
+    }
+
+    #[test]
+    fn broken_references_are_errors() {
+        let error = resolved(vec![var("A", "${B}"), var("B", "${A}")], vec![]).unwrap_err();
+        assert_eq!(error.to_string(), "invalid: cyclic reference A -> B -> A");
+
+        let error = resolved(vec![var("A", "${MISSING}")], vec![]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid: unresolved reference '${MISSING}' in 'A'"
+        );
+
+        let error = resolved(vec![var("A", "${B")], vec![]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "invalid: unterminated reference in '${B'"
+        );
+    }
+
+    #[test]
+    fn referencing_sensitive_variables_makes_sensitive() {
+        let variables = resolved(
+            vec![var("DSN", "user:${PASSWORD}@db"), var("HOST", "db")],
+            vec![EnvVar {
+                sensitive: true,
+                ..var("PASSWORD", "hunter2")
+            }],
+        )
+        .unwrap();
+        assert_eq!(values(&variables), vec!["user:hunter2@db", "db"]);
+        assert!(variables[0].sensitive);
+        assert!(!variables[1].sensitive);
+    }
+
+    #[test]
+    fn references_read_the_referenced_value() {
+        let file = std::env::temp_dir().join(format!("mental-interpolate-{}", std::process::id()));
+        std::fs::write(&file, "from file\n").unwrap();
+        let variables = resolved(
+            vec![
+                EnvVar::new(
+                    "CONTENT",
+                    Value::Reference(Reference::File { file: file.clone() }),
+                ),
+                var("QUOTED", "'${CONTENT}'"),
+            ],
+            vec![],
+        )
+        .unwrap();
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(values(&variables), vec!["from file", "'from file'"]);
+    }
+}
@@ -0,0 +1,247 @@
+//! Checks of the quality of the config
+
+use crate::components::is_posix_name;
+use crate::config::MentalConfig;
+use crate::mapping::MentalMapping;
+use clap::ValueEnum;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// How bad a finding is, errors make `lint` fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Severity {
+    Error,
+    Warning,
+}
+
+/// Format the findings are reported in
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ReportFormat {
+    /// one line per finding
+    #[default]
+    Human,
+    /// single JSON object for CI
+    Json,
+}
+
+/// Problem found in the config
+///
+/// * `rule`: identifier of the check that found the problem
+/// * `severity`: how bad the problem is
+/// * `component`: component the problem was found in
+/// * `message`: description of the problem
+#[derive(Debug, Serialize)]
+pub(crate) struct Finding {
+    pub(crate) rule: &'static str,
+    pub(crate) severity: Severity,
+    pub(crate) component: String,
+    pub(crate) message: String,
+}
+
+impl fmt::Display for Finding {
+    /// Format the finding as a single line
+    ///
+    /// * `f`: formatter
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        write!(
+            f,
+            "{}: {}: {} [{}]",
+            severity, self.component, self.message, self.rule
+        )
+    }
+}
+
+/// Report of all findings
+///
+/// * `findings`: findings in the order of the components
+/// * `errors`: number of findings with severity error
+/// * `warnings`: number of findings with severity warning
+#[derive(Debug, Serialize)]
+pub(crate) struct Report {
+    pub(crate) findings: Vec<Finding>,
+    pub(crate) errors: usize,
+    pub(crate) warnings: usize,
+}
+
+/// Check the config for problems
+///
+/// Reports duplicate components, duplicate keys, names that are no POSIX variable names,
+/// keys whose casing differs from the upper cased prefix, empty components, patterns that
+/// do not compile and, if mappings are given, components that are neither mapped nor
+/// extended by a mapped one.
+///
+/// * `config`: config to check
+/// * `mappings`: mappings the components are used in, the unused check is skipped if empty
+pub(crate) fn lint(config: &MentalConfig, mappings: &[MentalMapping]) -> Report {
+    let mut findings: Vec<Finding> = Vec::new();
+    let mut finding = |rule, severity, component: &str, message: String| {
+        findings.push(Finding {
+            rule,
+            severity,
+            component: component.to_string(),
+            message,
+        })
+    };
+
+    let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+    for c in config.components() {
+        *counts.entry(c.name.as_str()).or_default() += 1;
+    }
+    let mut reported: BTreeSet<&str> = BTreeSet::new();
+
+    for c in config.components() {
+        let count = counts[c.name.as_str()];
+        if count > 1 && reported.insert(&c.name) {
+            finding(
+                "duplicate-component",
+                Severity::Error,
+                &c.name,
+                format!("component is defined {} times", count),
+            );
+        }
+        if c.is_empty() {
+            finding(
+                "empty-component",
+                Severity::Warning,
+                &c.name,
+                "component has no values".into(),
+            );
+        }
+
+        let prefix = c.applied_prefix();
+        for (profile, keys) in c.key_lists() {
+            let location = match profile {
+                Some(profile) => format!(" in profile '{}'", profile),
+                None => String::new(),
+            };
+            let mut seen: BTreeSet<&str> = BTreeSet::new();
+            for key in keys {
+                if !seen.insert(key) {
+                    finding(
+                        "duplicate-key",
+                        Severity::Error,
+                        &c.name,
+                        format!("key '{}' is defined more than once{}", key, location),
+                    );
+                    continue;
+                }
+                let name = format!("{}{}", prefix, key);
+                if !is_posix_name(&name) {
+                    finding(
+                        "invalid-name",
+                        Severity::Error,
+                        &c.name,
+                        format!("'{}'{} is no valid POSIX variable name", name, location),
+                    );
+                } else if !prefix.is_empty() && key != key.to_uppercase() {
+                    finding(
+                        "inconsistent-casing",
+                        Severity::Warning,
+                        &c.name,
+                        format!(
+                            "key '{}'{} is not upper case like the prefix '{}'",
+                            key, location, prefix
+                        ),
+                    );
+                }
+            }
+        }
+
+        for (profile, key, rules) in c.rules() {
+            if let Some(error) = rules.pattern_error() {
+                let location = match profile {
+                    Some(profile) => format!(" in profile '{}'", profile),
+                    None => String::new(),
+                };
+                finding(
+                    "invalid-pattern",
+                    Severity::Error,
+                    &c.name,
+                    format!(
+                        "pattern of key '{}'{} does not compile: {}",
+                        key, location, error
+                    ),
+                );
+            }
+        }
+    }
+
+    if !mappings.is_empty() {
+        let mut used: BTreeSet<&str> = BTreeSet::new();
+        let mut pending: Vec<&str> = mappings
+            .iter()
+            .flat_map(|m| &m.mappings)
+            .flat_map(|m| &m.components)
+            .map(String::as_str)
+            .collect();
+        while let Some(name) = pending.pop() {
+            if used.insert(name) {
+                for c in config.components().iter().filter(|c| c.name == name) {
+                    pending.extend(c.extends.iter().map(String::as_str));
+                }
+            }
+        }
+        let mut reported: BTreeSet<&str> = BTreeSet::new();
+        for c in config.components() {
+            if !used.contains(c.name.as_str()) && reported.insert(&c.name) {
+                finding(
+                    "unused-component",
+                    Severity::Warning,
+                    &c.name,
+                    "component is not used by any mapping".into(),
+                );
+            }
+        }
+    }
+
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .count();
+    let warnings = findings.len() - errors;
+    Report {
+        findings,
+        errors,
+        warnings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_that_do_not_compile_are_errors() {
+        let config: MentalConfig = serde_yaml::from_str(
+            r#"
+components:
+  - name: app
+    prefix: app
+    values:
+      - name: MODE
+        value: fast
+        rules:
+          pattern: "(fast|slow"
+    profiles:
+      prod:
+        - name: MODE
+          rules:
+            pattern: "fast|slow"
+"#,
+        )
+        .unwrap();
+        let report = lint(&config, &[]);
+        let rules: Vec<&str> = report.findings.iter().map(|f| f.rule).collect();
+        assert_eq!(rules, vec!["invalid-pattern"]);
+        assert_eq!(report.errors, 1);
+        assert!(report.findings[0]
+            .message
+            .starts_with("pattern of key 'MODE' does not compile"));
+    }
+}
@@ -1,41 +1,71 @@
+use crate::error::MentalError;
+use crate::format::RenderOptions;
 use crate::{config::MentalConfig, mapping::MentalMapping};
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use mapping::FileIO;
+use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
 
 mod cli;
 mod components;
 mod config;
+mod diff;
+mod dotenv;
+mod error;
+mod format;
+mod hook;
+mod interpolate;
+mod lint;
 mod mapping;
+mod overrides;
+mod quoting;
+mod references;
+mod rules;
+mod run;
+mod secrets;
 mod util;
+mod validate;
 
 /// Main function of the cli
 fn main() {
     let cli = cli::Cli::parse();
+    if let Err(error) = run(cli) {
+        eprintln!("Error: {}", error);
+        std::process::exit(error.exit_code());
+    }
+}
+
+/// Run the parsed command
+///
+/// * `cli`: parsed command line arguments
+fn run(cli: cli::Cli) -> Result<(), MentalError> {
     let config_file = match cli.config.as_deref() {
         None => {
             let config_file: &Path = Path::new("./mental.yaml");
             config_file
         }
         Some(config_path) => {
-            println!("Setting config to :{}", config_path.display());
+            // the hook passes the config on every prompt
+            if !matches!(cli.command, Some(cli::Commands::HookEnv { .. })) {
+                eprintln!("Setting config to :{}", config_path.display());
+            }
             config_path
         }
     };
 
     // load the config
-    let mental_config = match MentalConfig::from_file(&config_file) {
-        Ok(config) => config,
-        Err(error) => panic!("Problem opening the file: {:?}", error),
-    };
+    let mental_config = MentalConfig::from_file(&config_file)?;
 
     // match subcommands
     match &cli.command {
-        Some(cli::Commands::Schema { target }) => match target {
-            None => panic!("Target could not be resolved"),
-            Some(target) => {
-                MentalConfig::create_schema(target).expect("Error writing file");
+        Some(cli::Commands::Schema { target, mapping }) => match target {
+            None => {
+                return Err(MentalError::Validation(
+                    "no target given for the schema".into(),
+                ))
             }
+            Some(target) if *mapping => MentalMapping::create_schema(target)?,
+            Some(target) => MentalConfig::create_schema(target)?,
         },
         Some(cli::Commands::Component { component }) => match component {
             cli::Component::List {} => {
@@ -45,58 +75,76 @@ fn main() {
                     println!("  {}", &c)
                 }
             }
-            cli::Component::Show { names } => {
-                let components = mental_config.to_env(names);
+            cli::Component::Show {
+                names,
+                format,
+                quoting,
+                profile,
+                reveal,
+            } => {
+                let options = RenderOptions {
+                    format: *format,
+                    quoting: *quoting,
+                    profile: profile.clone(),
+                    mask: !reveal,
+                };
+                let components = mental_config.to_env(names, &options)?;
                 for env_entry in components {
                     println!("{}", env_entry);
                 }
             }
             cli::Component::Create {
                 name,
-                prefix,
-                keys,
                 values,
+                reference,
+                prefix,
+                from_file,
+                from_stdin,
             } => {
-                println!("Creating component");
-
-                if keys.is_empty() && values.is_empty() {
-                    panic!("No keys and values given. Aborting creation of component")
-                }
-
-                if keys.len() != values.len() {
-                    panic!("Number of keys and values is not equal.")
-                }
-                let mut key_values: Vec<(String, String)> = Vec::new();
-                for (key, value) in keys.iter().zip(values.iter()) {
-                    key_values.push((key.to_owned(), value.to_owned()))
-                }
-
-                match prefix {
-                    Some(prefix) => {
-                        match mental_config.create_component_with_prefix(
-                            name.to_owned(),
-                            prefix.to_owned(),
-                            key_values,
-                        ) {
-                            Ok(config) => {
-                                println!("Created component with prefix");
-                                config
-                                    .dump(&config_file.to_path_buf())
-                                    .expect("Error writing config");
-                            }
-                            Err(err) => panic!("Error creating component {}", err),
-                        }
+                let read: Vec<(String, String)> = match (from_file, from_stdin) {
+                    (Some(file), _) => {
+                        let content =
+                            std::fs::read_to_string(file).map_err(|e| MentalError::io(file, e))?;
+                        dotenv::parse(&content).map_err(|e| e.into_error(file))?
                     }
-                    None => match mental_config.create_component(name.to_owned(), key_values) {
-                        Ok(config) => {
-                            println!("Created component");
-                            config
-                                .dump(&config_file.to_path_buf())
-                                .expect("Error writing config");
-                        }
-                        Err(err) => panic!("Error creating component {}", err),
-                    },
+                    (None, true) => {
+                        let stdin = Path::new("<stdin>");
+                        let content = std::io::read_to_string(std::io::stdin())
+                            .map_err(|e| MentalError::io(stdin, e))?;
+                        dotenv::parse(&content).map_err(|e| e.into_error(stdin))?
+                    }
+                    (None, false) => Vec::new(),
+                };
+                let mut key_values: Vec<(String, components::Value)> = read
+                    .into_iter()
+                    .map(|(key, value)| (key, components::Value::parse(value)))
+                    .collect();
+                for (key, value) in values {
+                    let value = match reference {
+                        true => components::Value::parse_reference(value.to_owned())?,
+                        false => components::Value::parse(value.to_owned()),
+                    };
+                    match key_values.iter_mut().find(|(k, _)| k == key) {
+                        Some(existing) => existing.1 = value,
+                        None => key_values.push((key.to_owned(), value)),
+                    }
+                }
+                if key_values.is_empty() {
+                    return Err(MentalError::Validation(
+                        "no values given, aborting creation of component".into(),
+                    ));
+                }
+
+                let created = match prefix {
+                    Some(prefix) => mental_config.create_component_with_prefix(
+                        name.to_owned(),
+                        prefix.to_owned(),
+                        key_values,
+                    ),
+                    None => mental_config.create_component(name.to_owned(), key_values),
                 };
+                created?.dump(&config_file.to_path_buf())?;
+                println!("Created component {}", name);
             }
             &cli::Component::FromEnv {} => {
                 use std::env;
@@ -106,19 +154,16 @@ fn main() {
                     env_variables.push(format!("{key}: {value}"));
                 }
 
-                let selected_env_key_values = match cli::format_multiline_list(
+                let selected_env_key_values = cli::format_multiline_list(
                     env_variables,
                     "Select variables you want to import",
-                ) {
-                    Ok(selection) => selection,
-                    Err(error) => panic!("Problem opening the file: {:?}", error),
-                };
+                )?;
 
                 if selected_env_key_values.is_empty() {
-                    panic!("No component selected");
+                    return Err(MentalError::Validation("no variables selected".into()));
                 }
 
-                let mut key_values: Vec<(String, String)> = Vec::new();
+                let mut key_values: Vec<(String, components::Value)> = Vec::new();
                 println!("Selected variables");
                 for key_value in selected_env_key_values {
                     let key_value_split = key_value.split(": ");
@@ -127,63 +172,640 @@ fn main() {
                     let key = parts.first().unwrap().to_string();
                     let value = parts.last().unwrap().to_string();
                     println!("{}:{}", &key, &value);
-                    key_values.push((key, value));
+                    key_values.push((key, components::Value::parse(value)));
                 }
                 let component_name: String =
-                    match inquire::Text::new("Please select a name for the component").prompt() {
-                        Ok(name) => name,
-                        Err(_) => panic!("Not a valid name"),
-                    };
-                match mental_config.create_component(component_name, key_values) {
-                    Ok(config) => {
-                        println!("Created component");
-                        config
-                            .dump(&config_file.to_path_buf())
-                            .expect("Error writing config");
+                    inquire::Text::new("Please select a name for the component").prompt()?;
+                let config = mental_config.create_component(component_name, key_values)?;
+                println!("Created component");
+                config.dump(&config_file.to_path_buf())?;
+            }
+            cli::Component::Set {
+                component,
+                values,
+                secret,
+                reference,
+            } => {
+                let config =
+                    mental_config.set_values(component, values.to_owned(), *secret, *reference)?;
+                config.dump(&config_file.to_path_buf())?;
+                println!("Updated component {}", component);
+            }
+            cli::Component::Unset { component, keys } => {
+                let config = mental_config.unset_values(component, keys)?;
+                config.dump(&config_file.to_path_buf())?;
+                config.save_overrides()?;
+                println!("Updated component {}", component);
+            }
+            cli::Component::SetPrefix { component, prefix } => {
+                let config = mental_config.set_prefix(component, prefix.to_owned())?;
+                config.dump(&config_file.to_path_buf())?;
+                println!("Updated component {}", component);
+            }
+            cli::Component::Delete { component, mapping } => {
+                let mapping_files = mapping_files_or_default(config_file, mapping)?;
+                let mut using: Vec<String> = Vec::new();
+                for mapping_file in &mapping_files {
+                    let loaded_mapping = MentalMapping::from_file(&mapping_file.as_path())?;
+                    if loaded_mapping.uses_component(component) {
+                        using.push(mapping_file.display().to_string());
+                    }
+                }
+                if !using.is_empty() {
+                    return Err(MentalError::Conflict(format!(
+                        "component '{}' is mapped in {}",
+                        component,
+                        using.join(", ")
+                    )));
+                }
+                let config = mental_config.delete_component(component)?;
+                config.dump(&config_file.to_path_buf())?;
+                config.save_overrides()?;
+                println!("Deleted component {}", component);
+            }
+            cli::Component::Rename {
+                component,
+                name,
+                mapping,
+            } => {
+                let mapping_files = mapping_files_or_default(config_file, mapping)?;
+                // load all mapping files first, a broken one must not leave a half renamed state
+                let mut mappings: Vec<(&PathBuf, MentalMapping)> = Vec::new();
+                for mapping_file in &mapping_files {
+                    mappings.push((
+                        mapping_file,
+                        MentalMapping::from_file(&mapping_file.as_path())?,
+                    ));
+                }
+                let config = mental_config.rename_component(component, name)?;
+                config.dump(&config_file.to_path_buf())?;
+                config.save_overrides()?;
+                println!("Renamed component {} to {}", component, name);
+                for (mapping_file, mut loaded_mapping) in mappings {
+                    if loaded_mapping.rename_component(component, name) {
+                        loaded_mapping.dump(mapping_file)?;
+                        println!("Updated {}", mapping_file.display());
                     }
-                    Err(err) => panic!("Error creating component {}", err),
                 }
             }
+            cli::Component::Copy {
+                source,
+                destination,
+            } => {
+                let config = mental_config.copy_component(source, destination)?;
+                config.dump(&config_file.to_path_buf())?;
+                config.save_overrides()?;
+                println!("Copied component {} to {}", source, destination);
+            }
+            cli::Component::Import { file, name, prefix } => {
+                let content =
+                    std::fs::read_to_string(file).map_err(|e| MentalError::io(file, e))?;
+                let mut key_values = dotenv::parse(&content).map_err(|e| e.into_error(file))?;
+                if key_values.is_empty() {
+                    return Err(MentalError::Validation(format!(
+                        "no variables found in {}",
+                        file.display()
+                    )));
+                }
+
+                if let Some(prefix) = prefix {
+                    let shared = format!("{}_", prefix.to_uppercase());
+                    let unprefixed: Vec<&str> = key_values
+                        .iter()
+                        .filter(|(key, _)| !key.to_uppercase().starts_with(&shared))
+                        .map(|(key, _)| key.as_str())
+                        .collect();
+                    if !unprefixed.is_empty() {
+                        return Err(MentalError::Validation(format!(
+                            "keys without prefix '{}': {}",
+                            shared,
+                            unprefixed.join(", ")
+                        )));
+                    }
+                    for (key, _) in key_values.iter_mut() {
+                        *key = key[shared.len()..].to_string();
+                    }
+                }
+                let key_values: Vec<(String, components::Value)> = key_values
+                    .into_iter()
+                    .map(|(key, value)| (key, components::Value::parse(value)))
+                    .collect();
+
+                let component_name: String = match name {
+                    Some(name) => name.to_owned(),
+                    None => {
+                        inquire::Text::new("Please select a name for the component").prompt()?
+                    }
+                };
+                let created = match prefix {
+                    Some(prefix) => mental_config.create_component_with_prefix(
+                        component_name,
+                        prefix.to_owned(),
+                        key_values,
+                    ),
+                    None => mental_config.create_component(component_name, key_values),
+                };
+                created?.dump(&config_file.to_path_buf())?;
+                println!("Imported component from {}", file.display());
+            }
         },
-        Some(cli::Commands::Map { target }) => {
+        Some(cli::Commands::Secrets { secrets }) => match secrets {
+            cli::Secrets::GenerateKey {} => {
+                let key_file = mental_config.keyring().key_file().to_path_buf();
+                if key_file.exists() {
+                    return Err(MentalError::Conflict(format!(
+                        "key file {} already exists",
+                        key_file.display()
+                    )));
+                }
+                if mental_config.has_secrets() {
+                    return Err(MentalError::Conflict(
+                        "the config already holds secrets, use `secrets rotate-key` to move them to a key file".into(),
+                    ));
+                }
+                secrets::write_key_file(&key_file, &secrets::generate_key())?;
+                secrets::ignore_key_file(&key_file)?;
+                println!("Created key file {}", key_file.display());
+            }
+            cli::Secrets::RotateKey { passphrase } => {
+                let key_file = mental_config.keyring().key_file().to_path_buf();
+                if mental_config.has_secrets() {
+                    mental_config.keyring().unlock_current()?;
+                }
+                let new_key = match passphrase {
+                    true => secrets::prompt_passphrase("New passphrase of the secrets:", true)?,
+                    false => secrets::generate_key().into_bytes(),
+                };
+                let keyring = secrets::Keyring::with_master(&key_file, new_key.clone());
+                let (config, count) = mental_config.rotate_secrets(keyring)?;
+
+                // the old key stays in place until the config is written
+                let new_key_file = key_file.with_extension("key.new");
+                if !passphrase {
+                    secrets::write_key_file(&new_key_file, &String::from_utf8_lossy(&new_key))?;
+                }
+                config.dump(&config_file.to_path_buf())?;
+                if !passphrase {
+                    std::fs::rename(&new_key_file, &key_file)
+                        .map_err(|e| MentalError::io(&key_file, e))?;
+                    secrets::ignore_key_file(&key_file)?;
+                } else if key_file.exists() {
+                    // a remaining key file would be used instead of the passphrase
+                    std::fs::remove_file(&key_file).map_err(|e| MentalError::io(&key_file, e))?;
+                    println!("Removed the old key file {}", key_file.display());
+                }
+                println!("Rotated the key of {} secrets", count);
+            }
+        },
+        Some(cli::Commands::Run {
+            components,
+            mapping,
+            target,
+            profile,
+            keep_existing,
+            command,
+        }) => {
+            let components = match (mapping, target) {
+                (Some(mapping), Some(target)) => {
+                    let loaded_mapping = MentalMapping::from_file(&mapping.as_path())?;
+                    let components = loaded_mapping.components_for(target);
+                    if components.is_empty() {
+                        return Err(MentalError::Validation(format!(
+                            "no mapping for target {}",
+                            target.display()
+                        )));
+                    }
+                    components
+                }
+                _ => components.to_owned(),
+            };
+            let variables = mental_config.to_env_map(&components, profile.as_deref())?;
+            run::exec(command, &variables, *keep_existing)?;
+        }
+        Some(cli::Commands::Hook {
+            shell,
+            mapping,
+            profile,
+        }) => {
+            let mapping_files = mapping_files_or_default(config_file, mapping)?;
+            let absolute =
+                |path: &Path| std::fs::canonicalize(path).map_err(|e| MentalError::io(path, e));
+            let executable =
+                std::env::current_exe().map_err(|e| MentalError::io(Path::new("mental"), e))?;
+            let mut words: Vec<String> = vec![
+                executable.to_string_lossy().into_owned(),
+                "--config".into(),
+                absolute(config_file)?.to_string_lossy().into_owned(),
+                "hook-env".into(),
+                shell.to_possible_value().unwrap().get_name().into(),
+            ];
+            if let Some(profile) = profile {
+                words.extend(["--profile".into(), profile.to_owned()]);
+            }
+            for mapping_file in &mapping_files {
+                words.extend([
+                    "--mapping".into(),
+                    absolute(mapping_file)?.to_string_lossy().into_owned(),
+                ]);
+            }
+            print!("{}", shell.snippet(&shell.command(&words)));
+        }
+        Some(cli::Commands::HookEnv {
+            shell,
+            mapping,
+            profile,
+        }) => {
+            let dir = std::env::current_dir()
+                .and_then(std::fs::canonicalize)
+                .map_err(|e| MentalError::io(Path::new("."), e))?;
+            if hook::is_current(&dir) {
+                return Ok(());
+            }
+            let (target, variables) = match hook::find_target(mapping, &dir)? {
+                Some((target, components)) => (
+                    Some(target),
+                    mental_config.to_env_map(&components, profile.as_deref())?,
+                ),
+                None => (None, Vec::new()),
+            };
+            let (lines, changes) = hook::env_script(*shell, &variables, &dir)?;
+            for line in lines {
+                println!("{}", line);
+            }
+            if !changes.is_empty() {
+                let target = match target {
+                    Some(target) => target.display().to_string(),
+                    None => "unloaded".into(),
+                };
+                eprintln!("mental: {} {}", target, changes.join(" "));
+            }
+        }
+        Some(cli::Commands::Validate { mapping }) => {
+            let mapping_files = mapping_files_or_default(config_file, mapping)?;
+            let mut count = 0;
+            for mapping_file in &mapping_files {
+                let loaded_mapping = MentalMapping::from_file(&mapping_file.as_path())?;
+                for problem in validate::validate(&loaded_mapping, &mental_config, &[]) {
+                    println!("{}: {}", mapping_file.display(), problem);
+                    count += 1;
+                }
+            }
+            if count > 0 {
+                return Err(MentalError::Validation(format!(
+                    "{} problems found in {} mapping files",
+                    count,
+                    mapping_files.len()
+                )));
+            }
+            println!("{} mapping files are valid", mapping_files.len());
+        }
+        Some(cli::Commands::Lint { format, mapping }) => {
+            let mapping_files = mapping_files_or_default(config_file, mapping)?;
+            let mappings = mapping_files
+                .iter()
+                .map(|f| MentalMapping::from_file(&f.as_path()))
+                .collect::<Result<Vec<_>, _>>()?;
+            let report = lint::lint(&mental_config, &mappings);
+            match format {
+                lint::ReportFormat::Human => {
+                    for finding in &report.findings {
+                        println!("{}", finding);
+                    }
+                    println!("{} errors, {} warnings", report.errors, report.warnings);
+                }
+                lint::ReportFormat::Json => println!(
+                    "{}",
+                    serde_json::to_string_pretty(&report).map_err(|e| MentalError::Validation(
+                        format!("could not serialize report: {}", e)
+                    ))?
+                ),
+            }
+            if report.errors > 0 {
+                return Err(MentalError::Validation(format!(
+                    "config has {} errors",
+                    report.errors
+                )));
+            }
+        }
+        Some(cli::Commands::Map {
+            action: Some(action),
+            ..
+        }) => {
+            let mapping_file = |given: &Option<PathBuf>| -> Result<PathBuf, MentalError> {
+                if let Some(given) = given {
+                    return Ok(given.to_owned());
+                }
+                let files = util::mapping_files(config_folder(config_file))?;
+                match files.as_slice() {
+                    [] => Err(MentalError::Validation(
+                        "no mapping file next to the config, pass one with --mapping".into(),
+                    )),
+                    [file] => Ok(file.to_owned()),
+                    _ => {
+                        let names = files.iter().map(|f| f.display().to_string()).collect();
+                        Ok(PathBuf::from(cli::select(names, "Select a mapping file:")?))
+                    }
+                }
+            };
+            let select_components = |given: &[String], target: &Path| {
+                let components = match given.is_empty() {
+                    true => cli::module_multiselect(
+                        mental_config.list_components(),
+                        &format!(
+                            "Select components that should be included in folder '{}'. Components: ",
+                            target.display()
+                        ),
+                    )?,
+                    false => given.to_vec(),
+                };
+                mental_config.check_components(&components)?;
+                Ok::<Vec<String>, MentalError>(components)
+            };
+            let select_target = |given: &Option<PathBuf>, loaded: &MentalMapping| match given {
+                Some(path) => Ok::<PathBuf, MentalError>(path.to_owned()),
+                None => {
+                    let targets = loaded
+                        .list_targets()
+                        .iter()
+                        .map(|t| t.display().to_string())
+                        .collect();
+                    Ok(PathBuf::from(cli::select(targets, "Select a target:")?))
+                }
+            };
+
+            match action {
+                cli::MapAction::List { mapping } => {
+                    let file = mapping_file(mapping)?;
+                    let loaded = MentalMapping::from_file(&file.as_path())?;
+                    for m in &loaded.mappings {
+                        match &m.file {
+                            Some(output) => println!(
+                                "{} ({}): {}",
+                                m.path.display(),
+                                output.display(),
+                                m.components.join(", ")
+                            ),
+                            None => println!("{}: {}", m.path.display(), m.components.join(", ")),
+                        }
+                    }
+                }
+                cli::MapAction::Add {
+                    path,
+                    components,
+                    file: output,
+                    mapping,
+                    discovery,
+                } => {
+                    let file = mapping_file(mapping)?;
+                    let mut loaded = match file.exists() {
+                        true => MentalMapping::from_file(&file.as_path())?,
+                        false => MentalMapping::default(),
+                    };
+                    let target = match path {
+                        Some(path) => path.to_owned(),
+                        None => {
+                            let folders = util::list_folders(
+                                config_folder(config_file),
+                                &discovery.discovery(),
+                            )?
+                            .iter()
+                            .map(|f| f.display().to_string())
+                            .collect();
+                            PathBuf::from(cli::select(folders, "Select the target folder:")?)
+                        }
+                    };
+                    let components = select_components(components, &target)?;
+                    loaded.add(&target, components, output.as_deref())?;
+                    loaded.dump(&file)?;
+                    println!("Mapped {} in {}", target.display(), file.display());
+                }
+                cli::MapAction::Remove {
+                    path,
+                    file: output,
+                    mapping,
+                } => {
+                    let file = mapping_file(mapping)?;
+                    let mut loaded = MentalMapping::from_file(&file.as_path())?;
+                    let target = select_target(path, &loaded)?;
+                    loaded.remove(&target, output.as_deref())?;
+                    loaded.dump(&file)?;
+                    println!("Removed {} from {}", target.display(), file.display());
+                }
+                cli::MapAction::SetComponents {
+                    path,
+                    components,
+                    file: output,
+                    mapping,
+                } => {
+                    let file = mapping_file(mapping)?;
+                    let mut loaded = MentalMapping::from_file(&file.as_path())?;
+                    let target = select_target(path, &loaded)?;
+                    let components = select_components(components, &target)?;
+                    loaded.set_components(&target, components, output.as_deref())?;
+                    loaded.dump(&file)?;
+                    println!("Updated {} in {}", target.display(), file.display());
+                }
+            }
+        }
+        Some(cli::Commands::Map {
+            action: None,
+            target,
+            discovery,
+        }) => {
             let target_path = match target {
                 None => match config_file.parent() {
                     Some(parent) => parent,
-                    None => panic!("Could not resolve folder"),
+                    None => {
+                        return Err(MentalError::Validation(
+                            "could not resolve the folder of the config".into(),
+                        ))
+                    }
                 },
                 Some(target_folder) => target_folder,
             };
-            let mappings: MentalMapping =
-                MentalMapping::new(target_path, mental_config.list_components());
+            let mappings: MentalMapping = MentalMapping::new(
+                target_path,
+                mental_config.list_components(),
+                &discovery.discovery(),
+            )?;
 
-            let mapping_name: String =
-                match inquire::Text::new("Please select a name for the mapping").prompt() {
-                    Ok(name) => format!("./{}.map", name),
-                    Err(_) => panic!("Not a valid name"),
-                };
+            let mapping_name: String = format!(
+                "./{}.map",
+                inquire::Text::new("Please select a name for the mapping").prompt()?
+            );
 
             let mut mapping_file = PathBuf::new();
             mapping_file.push(mapping_name);
-            mappings.dump(&mapping_file).expect("Error writing config")
+            mappings.dump(&mapping_file)?
         }
         Some(cli::Commands::Apply {
             mapping,
             target,
             stdout,
+            check,
+            diff,
+            reveal,
+            format,
+            quoting,
+            profile,
+            each_profile,
         }) => {
-            let loaded_mapping = match MentalMapping::from_file(&mapping.as_path()) {
-                Ok(m) => m,
-                Err(error) => panic!("Problem opening the file: {:?}", error),
-            };
+            let loaded_mapping = MentalMapping::from_file(&mapping.as_path())?;
             let target_paths = match target {
                 None => loaded_mapping.list_targets(),
                 Some(target_folder) => vec![target_folder.to_owned()],
             };
+            let profiles: Vec<Option<String>> = match each_profile {
+                true => mental_config
+                    .list_profiles()
+                    .into_iter()
+                    .map(Some)
+                    .collect(),
+                false => vec![profile.clone()],
+            };
+            if profiles.is_empty() {
+                return Err(MentalError::Validation(
+                    "--each-profile needs at least one profile in the config".into(),
+                ));
+            }
+
+            let mut mapped: Vec<String> = Vec::new();
+            for target_path in &target_paths {
+                for component in loaded_mapping.components_for(target_path) {
+                    if mental_config.name_exists(&component) && !mapped.contains(&component) {
+                        mapped.push(component);
+                    }
+                }
+            }
+            let mut missing: Vec<components::Missing> = Vec::new();
+            for profile in &profiles {
+                for m in mental_config.missing_values(&mapped, profile.as_deref())? {
+                    if !missing
+                        .iter()
+                        .any(|o| o.component == m.component && o.key == m.key)
+                    {
+                        missing.push(m);
+                    }
+                }
+            }
+            let mut mental_config = mental_config;
+            if !missing.is_empty() {
+                // read-only modes only report what is missing
+                let read_only = *check || *diff || *stdout;
+                if read_only || !std::io::stdin().is_terminal() {
+                    let names: Vec<&str> = missing.iter().map(|m| m.name.as_str()).collect();
+                    return Err(MentalError::Validation(format!(
+                        "missing required values: {}",
+                        names.join(", ")
+                    )));
+                }
+                eprintln!("{} required values are missing", missing.len());
+                for m in &missing {
+                    let value = components::Value::parse(cli::prompt_missing(m)?);
+                    mental_config.set_override(&m.component, &m.key, value);
+                }
+                let save = inquire::Confirm::new(&format!(
+                    "Save the values to {}?",
+                    overrides::Overrides::path(mental_config.folder()).display()
+                ))
+                .with_default(false)
+                .prompt()?;
+                if save {
+                    let path = mental_config.overrides().save(mental_config.folder())?;
+                    eprintln!("Saved the values to {}", path.display());
+                }
+            }
+
+            validate::check(&validate::validate(
+                &loaded_mapping,
+                &mental_config,
+                &target_paths,
+            ))?;
+            let options_for = |profile: Option<String>| RenderOptions {
+                format: format.unwrap_or_default(),
+                quoting: *quoting,
+                profile,
+                mask: !reveal,
+            };
 
-            loaded_mapping
-                .apply(&mental_config, target_paths, stdout)
-                .expect("Error")
+            if *check || *diff {
+                let mut planned = Vec::new();
+                for profile in profiles {
+                    planned.extend(loaded_mapping.plan(
+                        &mental_config,
+                        &target_paths,
+                        &options_for(profile),
+                        *each_profile,
+                    )?);
+                }
+
+                if *check {
+                    let mut drifted = 0;
+                    for target_file in &planned {
+                        let status = if target_file.is_up_to_date() {
+                            "up to date"
+                        } else if target_file.current.is_none() {
+                            "missing"
+                        } else {
+                            "out of date"
+                        };
+                        if !target_file.is_up_to_date() {
+                            drifted += 1;
+                        }
+                        println!("{:<12}{}", status, target_file.path.display());
+                    }
+                    if drifted > 0 {
+                        return Err(MentalError::Drift(drifted));
+                    }
+                } else {
+                    let colored = std::io::stdout().is_terminal();
+                    let mut changes = 0;
+                    for target_file in &planned {
+                        if !target_file.is_up_to_date() {
+                            print!("{}", diff::unified_diff(target_file, colored, *reveal));
+                        }
+                        changes += diff::ChangeCount::of(target_file).total();
+                    }
+                    println!("{} targets, {} changes", planned.len(), changes);
+                }
+                return Ok(());
+            }
+
+            for profile in profiles {
+                loaded_mapping.apply(
+                    &mental_config,
+                    target_paths.clone(),
+                    stdout,
+                    &options_for(profile),
+                    *each_profile,
+                )?
+            }
         }
         None => {}
     }
+    Ok(())
+}
+
+/// Folder of the config file, mapping files and the key file are searched here
+///
+/// * `config_file`: path of the config
+fn config_folder(config_file: &Path) -> &Path {
+    match config_file.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// Mapping files given on the command line, or all mapping files next to the config
+///
+/// * `config_file`: path of the config
+/// * `mapping`: mapping files given on the command line
+fn mapping_files_or_default(
+    config_file: &Path,
+    mapping: &[PathBuf],
+) -> Result<Vec<PathBuf>, MentalError> {
+    match mapping.is_empty() {
+        true => util::mapping_files(config_folder(config_file)),
+        false => Ok(mapping.to_vec()),
+    }
 }
@@ -2,31 +2,195 @@
 
 use crate::cli;
 use crate::config::MentalConfig;
-use schemars::JsonSchema;
+use crate::error::MentalError;
+use crate::format::{OutputFormat, RenderOptions};
+use crate::util::Discovery;
+use schemars::{schema_for, JsonSchema};
 use serde::{Deserialize, Serialize};
 use serde_yaml::from_str;
+use std::collections::BTreeMap;
 use std::fs;
 use std::fs::read_to_string;
 use std::fs::File;
-use std::io::Write;
-use std::path::{Path, PathBuf};
+use std::io::{self, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// First line of the block managed by `apply`
+const BLOCK_BEGIN: &str = "# >>> mental managed block >>>";
+/// Last line of the block managed by `apply`
+const BLOCK_END: &str = "# <<< mental managed block <<<";
+
+/// Merge generated lines into the managed block of an existing file
+///
+/// Lines outside of the block are kept as they are. If the file has no block yet it is
+/// appended to the end of the file.
+///
+/// * `file`: path of the file, used in errors
+/// * `existing`: current content of the file
+/// * `generated`: lines to put inside the block
+/// * `at_start`: put a new block at the start instead of the end of the file
+fn merge_managed_block(
+    file: &Path,
+    existing: &str,
+    generated: &[String],
+    at_start: bool,
+) -> Result<String, MentalError> {
+    let lines: Vec<&str> = existing.lines().collect();
+    let begin = lines.iter().position(|l| l.trim_end() == BLOCK_BEGIN);
+    let end = lines.iter().position(|l| l.trim_end() == BLOCK_END);
+
+    let mut block: Vec<&str> = vec![BLOCK_BEGIN];
+    block.extend(generated.iter().map(|l| l.as_str()));
+    block.push(BLOCK_END);
+
+    let merged: Vec<&str> = match (begin, end) {
+        (Some(begin), Some(end)) if begin < end => {
+            let mut merged = lines[..begin].to_vec();
+            merged.extend(block);
+            merged.extend(&lines[end + 1..]);
+            merged
+        }
+        (None, None) if at_start => {
+            let mut merged = block;
+            if !lines.is_empty() {
+                merged.push("");
+            }
+            merged.extend(lines);
+            merged
+        }
+        (None, None) => {
+            let mut merged = lines;
+            while merged.last().is_some_and(|l| l.trim().is_empty()) {
+                merged.pop();
+            }
+            if !merged.is_empty() {
+                merged.push("");
+            }
+            merged.extend(block);
+            merged
+        }
+        _ => {
+            return Err(MentalError::Parse {
+                file: file.to_path_buf(),
+                line: begin.or(end).map(|l| l + 1),
+                column: None,
+                message: "managed block markers are incomplete or out of order".into(),
+            })
+        }
+    };
+    Ok(format!("{}\n", merged.join("\n")))
+}
 
 /// Mapping from components to path
 ///
-/// * `path`: target path the variables are mapped into
+/// * `path`: target path the variables are mapped into, relative to the mapping file
 /// * `components`: the components that should be mapped
+/// * `file`: file relative to `path` the variables are written into, defaults to the file
+///   of the format
+/// * `format`: format of the written file, defaults to the extension of `file`
 #[derive(Debug, Serialize, Deserialize, JsonSchema)]
 pub struct Mapping {
     pub(crate) path: PathBuf,
     pub(crate) components: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) file: Option<PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(crate) format: Option<OutputFormat>,
+}
+
+impl Mapping {
+    /// Check if the mapping writes into a file of a target
+    ///
+    /// * `target`: target path
+    /// * `file`: file relative to the target, any file of the target if `None`
+    fn writes_into(&self, target: &Path, file: Option<&Path>) -> bool {
+        same_target(&self.path, target)
+            && file.is_none_or(|file| {
+                let own = match &self.file {
+                    Some(own) => own.clone(),
+                    None => PathBuf::from(
+                        self.output_format(OutputFormat::default())
+                            .default_file_name(),
+                    ),
+                };
+                same_target(&own, file)
+            })
+    }
+
+    /// Format of the output
+    ///
+    /// Uses the configured format, the extension of the configured file or the given default.
+    ///
+    /// * `default`: format used if nothing is configured
+    pub(crate) fn output_format(&self, default: OutputFormat) -> OutputFormat {
+        self.format
+            .or_else(|| OutputFormat::from_file_name(self.file.as_deref()?))
+            .unwrap_or(default)
+    }
+
+    /// File the variables are written into
+    ///
+    /// * `base`: folder of the mapping file the target is relative to
+    /// * `format`: format of the output
+    /// * `profile`: profile put in front of the extension, e.g. `settings.staging.json`, or
+    ///   appended to dotfiles without one, e.g. `.env.staging`
+    pub(crate) fn output_file(
+        &self,
+        base: &Path,
+        format: OutputFormat,
+        profile: Option<&str>,
+    ) -> PathBuf {
+        let folder = base.join(&self.path);
+        let file = match &self.file {
+            Some(file) => folder.join(file),
+            None => folder.join(format.default_file_name()),
+        };
+        let (Some(profile), Some(stem)) = (profile, file.file_stem()) else {
+            return file;
+        };
+        let mut file_name = stem.to_os_string();
+        file_name.push(format!(".{}", profile));
+        if let Some(extension) = file.extension() {
+            file_name.push(".");
+            file_name.push(extension);
+        }
+        file.with_file_name(file_name)
+    }
 }
 
 /// Collection of mappings
 ///
 /// * `mappings`: collection of mappings
-#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
 pub struct MentalMapping {
     pub mappings: Vec<Mapping>,
+    /// folder of the mapping file, targets are relative to it
+    #[serde(skip)]
+    #[schemars(skip)]
+    folder: PathBuf,
+}
+
+/// Rendered output of a mapping for a single file
+///
+/// * `path`: file the variables are written into
+/// * `format`: format of the file
+/// * `current`: content currently on disk, `None` if the file does not exist
+/// * `rendered`: content of the file after applying the mapping
+/// * `sensitive`: names of the rendered variables and if they are sensitive
+#[derive(Debug)]
+pub(crate) struct TargetFile {
+    pub(crate) path: PathBuf,
+    pub(crate) format: OutputFormat,
+    pub(crate) current: Option<String>,
+    pub(crate) rendered: String,
+    pub(crate) sensitive: BTreeMap<String, bool>,
+}
+
+impl TargetFile {
+    /// Check if the file on disk already matches the rendered content
+    pub(crate) fn is_up_to_date(&self) -> bool {
+        self.current.as_deref() == Some(self.rendered.as_str())
+    }
 }
 
 /// trait for handling that handles Serialization and Deserialization of structs
@@ -34,23 +198,49 @@ pub trait FileIO: serde::Serialize {
     /// Dump the struct into a file
     ///
     /// * `target`: target file to write into
-    fn dump(&self, target: &PathBuf) -> std::io::Result<()> {
-        let mut file = File::create(target)?;
-        let struct_as_string = serde_yaml::to_string(self).unwrap();
-        file.write_all(struct_as_string.as_bytes())?;
+    fn dump(&self, target: &PathBuf) -> Result<(), MentalError> {
+        let struct_as_string = serde_yaml::to_string(self).map_err(|e| {
+            MentalError::Validation(format!("could not serialize {}: {}", target.display(), e))
+        })?;
+        let mut file = File::create(target).map_err(|e| MentalError::io(target, e))?;
+        file.write_all(struct_as_string.as_bytes())
+            .map_err(|e| MentalError::io(target, e))?;
+        Ok(())
+    }
+
+    /// Dump a json schema of the struct for validation
+    ///
+    /// * `target`: filepath to save the schema into
+    fn create_schema(target: &PathBuf) -> Result<(), MentalError>
+    where
+        Self: JsonSchema,
+    {
+        let schema = schema_for!(Self);
+        let formatted_schema = serde_json::to_string(&schema).map_err(|e| {
+            MentalError::Validation(format!(
+                "could not serialize schema {}: {}",
+                target.display(),
+                e
+            ))
+        })?;
+        let mut file = File::create(target).map_err(|e| MentalError::io(target, e))?;
+        file.write_all(formatted_schema.as_bytes())
+            .map_err(|e| MentalError::io(target, e))?;
         Ok(())
     }
 
     /// Load the given struct from a file
     ///
     /// * `struct_file`: file that contains the struct in a serialized format
-    fn from_file(struct_file: &&Path) -> Result<Self, Box<dyn std::error::Error>>
+    fn from_file(struct_file: &&Path) -> Result<Self, MentalError>
     where
         Self: Sized,
         for<'a> Self: Deserialize<'a>,
     {
-        let struct_input = read_to_string(struct_file)?;
-        let deserialized_struct: Self = from_str(&struct_input)?;
+        let struct_input =
+            read_to_string(struct_file).map_err(|e| MentalError::io(struct_file, e))?;
+        let deserialized_struct: Self =
+            from_str(&struct_input).map_err(|e| MentalError::yaml(struct_file, e))?;
         Ok(deserialized_struct)
     }
 }
@@ -58,15 +248,33 @@ pub trait FileIO: serde::Serialize {
 impl FileIO for MentalMapping {}
 
 impl MentalMapping {
+    /// Load a mapping file, its targets are resolved relative to its folder
+    ///
+    /// * `mapping_file`: path of the mapping file
+    pub fn from_file(mapping_file: &&Path) -> Result<MentalMapping, MentalError> {
+        let mut mapping: MentalMapping = FileIO::from_file(mapping_file)?;
+        mapping.folder = mapping_file.parent().unwrap_or(Path::new("")).to_path_buf();
+        Ok(mapping)
+    }
+
+    /// Folder of a target, resolved relative to the folder of the mapping file
+    ///
+    /// * `target`: target path as written in the mapping
+    pub(crate) fn target_folder(&self, target: &Path) -> PathBuf {
+        self.folder.join(target)
+    }
+
     /// Create a new mapping searching for targets in a path
     ///
     /// * `path`: path to search
     /// * `components`: collection of components
-    pub fn new(path: &Path, components: Vec<String>) -> MentalMapping {
-        let selected_folders = match cli::folder_multiselect(path) {
-            Ok(selection) => selection,
-            Err(error) => panic!("Problem opening the file: {:?}", error),
-        };
+    /// * `discovery`: depth, globs and marker files the offered folders are filtered with
+    pub fn new(
+        path: &Path,
+        components: Vec<String>,
+        discovery: &Discovery,
+    ) -> Result<MentalMapping, MentalError> {
+        let selected_folders = cli::folder_multiselect(path, discovery)?;
         println!("Selected folders {:?}", selected_folders);
 
         let mut mappings: Vec<Mapping> = Vec::new();
@@ -75,54 +283,420 @@ impl MentalMapping {
                 "Select components that should be included in folder '{}'. Components: ",
                 f
             );
-            let selected_components = match cli::module_multiselect(components.clone(), &message) {
-                Ok(selection) => selection,
-                Err(error) => panic!("Problem opening the file: {:?}", error),
-            };
+            let selected_components = cli::module_multiselect(components.clone(), &message)?;
             let mut path_buffer = PathBuf::new();
             path_buffer.push(f);
             mappings.push(Mapping {
                 path: path_buffer,
                 components: selected_components.clone(),
+                file: None,
+                format: None,
             });
         }
-        MentalMapping { mappings }
+        Ok(MentalMapping {
+            mappings,
+            folder: PathBuf::new(),
+        })
+    }
+
+    /// Render the files for the given targets without writing them
+    ///
+    /// * `config`: deserialized config
+    /// * `targets`: targets to render the env mapping for
+    /// * `options`: format of mappings that do not configure one, quoting and profile
+    /// * `profile_files`: write into one file per profile instead of the default file
+    pub(crate) fn plan(
+        &self,
+        config: &MentalConfig,
+        targets: &[PathBuf],
+        options: &RenderOptions,
+        profile_files: bool,
+    ) -> Result<Vec<TargetFile>, MentalError> {
+        let mut planned: Vec<TargetFile> = Vec::new();
+        for m in &self.mappings {
+            if targets.iter().any(|t| same_target(t, &m.path)) {
+                let format = m.output_format(options.format);
+                let sections = config.sections(&m.components, options.profile.as_deref())?;
+                let target_config_env = format.render(&sections, options.quoting)?;
+                let sensitive = sections
+                    .iter()
+                    .flat_map(|s| &s.variables)
+                    .map(|v| (v.name.clone(), v.sensitive))
+                    .collect();
+                let profile = options.profile.as_deref().filter(|_| profile_files);
+                let target_path = m.output_file(&self.folder, format, profile);
+                let current = match read_to_string(&target_path) {
+                    Ok(content) => Some(content),
+                    Err(error) if error.kind() == io::ErrorKind::NotFound => None,
+                    Err(error) => return Err(MentalError::io(&target_path, error)),
+                };
+                let rendered = match format.supports_comments() {
+                    true => merge_managed_block(
+                        &target_path,
+                        current.as_deref().unwrap_or_default(),
+                        &target_config_env,
+                        format == OutputFormat::Toml,
+                    )?,
+                    false => format!("{}\n", target_config_env.join("\n")),
+                };
+                planned.push(TargetFile {
+                    path: target_path,
+                    format,
+                    current,
+                    rendered,
+                    sensitive,
+                });
+            }
+        }
+        Ok(planned)
     }
 
     /// Apply previous generated mapping
     ///
+    /// The variables are written into a managed block inside the file of each mapping
+    /// (`<path>/.env` by default), everything outside of the block is left untouched.
+    /// Formats without comments overwrite the whole file.
+    ///
     /// * `config`: deserialized config
     /// * `targets`: targets to apply the env mapping to
     /// * `to_stdout`: only print to stdout instead of file
+    /// * `options`: format of mappings that do not configure one, quoting and profile
+    /// * `profile_files`: write into one file per profile instead of the default file
     pub(crate) fn apply(
         &self,
         config: &MentalConfig,
         targets: Vec<PathBuf>,
         to_stdout: &bool,
-    ) -> std::io::Result<()> {
-        for m in &self.mappings {
-            if targets.contains(&m.path) {
-                let target_config_env = config.to_env(&m.components);
-                if *to_stdout {
-                    for env_entry in &target_config_env {
+        options: &RenderOptions,
+        profile_files: bool,
+    ) -> Result<(), MentalError> {
+        if *to_stdout {
+            for m in &self.mappings {
+                if targets.iter().any(|t| same_target(t, &m.path)) {
+                    let mapping_options = RenderOptions {
+                        format: m.output_format(options.format),
+                        ..options.clone()
+                    };
+                    for env_entry in config.to_env(&m.components, &mapping_options)? {
                         println!("{}", env_entry);
                     }
-                } else {
-                    let formatted_path = format!("{}{}", &m.path.display(), "/.env");
-                    let target_path = PathBuf::from(formatted_path);
-                    fs::write(target_path, target_config_env.join("\n"))?;
                 }
             }
+            return Ok(());
+        }
+        for target_file in self.plan(config, &targets, options, profile_files)? {
+            if !target_file.is_up_to_date() {
+                fs::write(&target_file.path, &target_file.rendered)
+                    .map_err(|e| MentalError::io(&target_file.path, e))?;
+            }
         }
         Ok(())
     }
 
-    /// List the targets for a mapping
+    /// List the components mapped to a target
+    ///
+    /// * `target`: target path of the mappings
+    pub(crate) fn components_for(&self, target: &Path) -> Vec<String> {
+        let mut components: Vec<String> = Vec::new();
+        for m in self
+            .mappings
+            .iter()
+            .filter(|m| same_target(&m.path, target))
+        {
+            for c in &m.components {
+                if !components.contains(c) {
+                    components.push(c.clone());
+                }
+            }
+        }
+        components
+    }
+
+    /// List the targets for a mapping, each target once
     pub(crate) fn list_targets(&self) -> Vec<PathBuf> {
         let mut res: Vec<PathBuf> = Vec::new();
         for m in &self.mappings {
-            res.push(m.path.clone())
+            if !res.iter().any(|t| same_target(t, &m.path)) {
+                res.push(m.path.clone())
+            }
         }
         res
     }
+
+    /// Map components to a new target
+    ///
+    /// A target may be mapped several times into different files.
+    ///
+    /// * `target`: target path
+    /// * `components`: components mapped to the target
+    /// * `file`: file relative to the target, defaults to the file of the default format
+    pub(crate) fn add(
+        &mut self,
+        target: &Path,
+        components: Vec<String>,
+        file: Option<&Path>,
+    ) -> Result<(), MentalError> {
+        let output = file.unwrap_or(Path::new(OutputFormat::default().default_file_name()));
+        if self
+            .mappings
+            .iter()
+            .any(|m| m.writes_into(target, Some(output)))
+        {
+            return Err(MentalError::Conflict(format!(
+                "target '{}' is already mapped into {}",
+                target.display(),
+                output.display()
+            )));
+        }
+        self.mappings.push(Mapping {
+            path: target.to_path_buf(),
+            components,
+            file: file.map(Path::to_path_buf),
+            format: None,
+        });
+        Ok(())
+    }
+
+    /// Remove the mappings of a target
+    ///
+    /// Returns the number of removed mappings.
+    ///
+    /// * `target`: target path
+    /// * `file`: file relative to the target, all mappings of the target if `None`
+    pub(crate) fn remove(
+        &mut self,
+        target: &Path,
+        file: Option<&Path>,
+    ) -> Result<usize, MentalError> {
+        let count = self.mappings.len();
+        self.mappings.retain(|m| !m.writes_into(target, file));
+        match count - self.mappings.len() {
+            0 => Err(unmapped(target, file)),
+            removed => Ok(removed),
+        }
+    }
+
+    /// Replace the components mapped to a target
+    ///
+    /// Returns the number of updated mappings.
+    ///
+    /// * `target`: target path
+    /// * `components`: components mapped to the target
+    /// * `file`: file relative to the target, all mappings of the target if `None`
+    pub(crate) fn set_components(
+        &mut self,
+        target: &Path,
+        components: Vec<String>,
+        file: Option<&Path>,
+    ) -> Result<usize, MentalError> {
+        let mut updated = 0;
+        for m in self.mappings.iter_mut() {
+            if m.writes_into(target, file) {
+                m.components = components.clone();
+                updated += 1;
+            }
+        }
+        match updated {
+            0 => Err(unmapped(target, file)),
+            updated => Ok(updated),
+        }
+    }
+
+    /// Check if any target is mapped to a component
+    ///
+    /// * `name`: name of the component
+    pub(crate) fn uses_component(&self, name: &str) -> bool {
+        self.mappings
+            .iter()
+            .any(|m| m.components.iter().any(|c| c == name))
+    }
+
+    /// Replace a component in all mappings
+    ///
+    /// Returns `true` if any mapping was changed.
+    ///
+    /// * `old`: current name of the component
+    /// * `new`: new name of the component
+    pub(crate) fn rename_component(&mut self, old: &str, new: &str) -> bool {
+        let mut changed = false;
+        for component in self
+            .mappings
+            .iter_mut()
+            .flat_map(|m| m.components.iter_mut())
+        {
+            if component == old {
+                *component = new.to_string();
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+/// Check if two paths point to the same target, ignoring `./` and trailing slashes
+///
+/// * `a`: first path
+/// * `b`: second path
+pub(crate) fn same_target(a: &Path, b: &Path) -> bool {
+    a.components()
+        .filter(|c| *c != Component::CurDir)
+        .eq(b.components().filter(|c| *c != Component::CurDir))
+}
+
+/// Error for a target without mapping
+///
+/// * `target`: target path
+fn unmapped(target: &Path, file: Option<&Path>) -> MentalError {
+    match file {
+        Some(file) => MentalError::Validation(format!(
+            "target '{}' is not mapped into {}",
+            target.display(),
+            file.display()
+        )),
+        None => MentalError::Validation(format!("target '{}' is not mapped", target.display())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Mapping of a folder into a `.env` and a `.env.json` file
+    fn two_outputs() -> MentalMapping {
+        let mut mapping = MentalMapping::default();
+        mapping
+            .add(Path::new("app"), vec!["db".into()], None)
+            .unwrap();
+        mapping
+            .add(
+                Path::new("./app"),
+                vec!["web".into()],
+                Some(Path::new(".env.json")),
+            )
+            .unwrap();
+        mapping
+    }
+
+    #[test]
+    fn targets_can_be_mapped_into_several_files() {
+        let mut mapping = two_outputs();
+        assert_eq!(mapping.mappings.len(), 2);
+        assert_eq!(mapping.list_targets(), vec![PathBuf::from("app")]);
+        assert_eq!(mapping.components_for(Path::new("app")), vec!["db", "web"]);
+
+        let again = mapping.add(
+            Path::new("app"),
+            vec!["other".into()],
+            Some(Path::new(".env")),
+        );
+        assert!(matches!(again, Err(MentalError::Conflict(_))));
+        assert_eq!(mapping.mappings.len(), 2);
+    }
+
+    #[test]
+    fn set_components_keeps_other_outputs() {
+        let mut mapping = two_outputs();
+        let updated = mapping
+            .set_components(
+                Path::new("app"),
+                vec!["cache".into()],
+                Some(Path::new(".env.json")),
+            )
+            .unwrap();
+        assert_eq!(updated, 1);
+        assert_eq!(mapping.mappings.len(), 2);
+        assert_eq!(mapping.mappings[0].components, vec!["db"]);
+        assert_eq!(mapping.mappings[1].components, vec!["cache"]);
+
+        let updated = mapping
+            .set_components(Path::new("app"), vec!["all".into()], None)
+            .unwrap();
+        assert_eq!(updated, 2);
+        assert!(mapping.mappings.iter().all(|m| m.components == ["all"]));
+
+        let missing = mapping.set_components(Path::new("app"), vec![], Some(Path::new(".env.sh")));
+        assert!(matches!(missing, Err(MentalError::Validation(_))));
+    }
+
+    #[test]
+    fn remove_selects_by_file() {
+        let mut mapping = two_outputs();
+        assert_eq!(
+            mapping
+                .remove(Path::new("app"), Some(Path::new(".env")))
+                .unwrap(),
+            1
+        );
+        assert_eq!(mapping.mappings.len(), 1);
+        assert_eq!(mapping.mappings[0].components, vec!["web"]);
+
+        let mut mapping = two_outputs();
+        assert_eq!(mapping.remove(Path::new("app"), None).unwrap(), 2);
+        assert!(mapping.remove(Path::new("app"), None).is_err());
+    }
+
+    #[test]
+    fn generated_lines_replace_the_managed_block() {
+        let generated = vec!["A=1".to_string()];
+        let file = Path::new(".env");
+        assert_eq!(
+            merge_managed_block(file, "KEEP=1\n\n", &generated, false).unwrap(),
+            format!("KEEP=1\n\n{}\nA=1\n{}\n", BLOCK_BEGIN, BLOCK_END)
+        );
+        assert_eq!(
+            merge_managed_block(file, "KEEP=1", &generated, true).unwrap(),
+            format!("{}\nA=1\n{}\n\nKEEP=1\n", BLOCK_BEGIN, BLOCK_END)
+        );
+
+        let existing = format!("X=1\n{}\nA=0\nB=0\n{}\nY=1\n", BLOCK_BEGIN, BLOCK_END);
+        assert_eq!(
+            merge_managed_block(file, &existing, &generated, false).unwrap(),
+            format!("X=1\n{}\nA=1\n{}\nY=1\n", BLOCK_BEGIN, BLOCK_END)
+        );
+    }
+
+    #[test]
+    fn incomplete_markers_are_parse_errors() {
+        let existing = format!("X=1\n{}\nA=0\n", BLOCK_BEGIN);
+        let error = merge_managed_block(Path::new(".env"), &existing, &[], false).unwrap_err();
+        assert!(matches!(error, MentalError::Parse { line: Some(2), .. }));
+        assert_eq!(error.exit_code(), crate::error::EXIT_PARSE);
+    }
+
+    #[test]
+    fn targets_are_relative_to_the_mapping_file() {
+        let folder = std::env::temp_dir().join(format!("mental-targets-{}", std::process::id()));
+        fs::create_dir_all(&folder).unwrap();
+        let file = folder.join("m.map");
+        fs::write(&file, "mappings: [{path: app, components: [db]}]").unwrap();
+
+        let mapping = MentalMapping::from_file(&file.as_path()).unwrap();
+        fs::remove_dir_all(&folder).unwrap();
+        assert_eq!(mapping.target_folder(Path::new("app")), folder.join("app"));
+        assert_eq!(
+            mapping.mappings[0].output_file(&mapping.folder, OutputFormat::Dotenv, None),
+            folder.join("app").join(".env")
+        );
+    }
+
+    #[test]
+    fn profiles_go_in_front_of_the_extension() {
+        let mapping: MentalMapping = serde_yaml::from_str(
+            "mappings: [{path: app, components: [db]}, {path: app, components: [db], file: settings.json}]",
+        )
+        .unwrap();
+        let file = |m: &Mapping, format| m.output_file(Path::new(""), format, Some("prod"));
+        assert_eq!(
+            file(&mapping.mappings[0], OutputFormat::Dotenv),
+            Path::new("app/.env.prod")
+        );
+        assert_eq!(
+            file(&mapping.mappings[0], OutputFormat::Json),
+            Path::new("app/.env.prod.json")
+        );
+        assert_eq!(
+            file(&mapping.mappings[1], OutputFormat::Json),
+            Path::new("app/settings.prod.json")
+        );
+    }
 }
@@ -0,0 +1,217 @@
+//! Local values overriding the config, kept out of version control
+
+use crate::components::Value;
+use crate::error::MentalError;
+use crate::util;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Name of the overrides file next to the config
+const OVERRIDES_FILE_NAME: &str = ".mental.local.yaml";
+
+/// Values of the local overrides file by component and key
+///
+/// The values replace the values of the config including those of profiles, keys the
+/// component does not define are ignored.
+///
+/// * `values`: values by component and key
+/// * `changed`: if the values differ from the overrides file
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub(crate) struct Overrides {
+    values: BTreeMap<String, BTreeMap<String, Value>>,
+    #[serde(skip)]
+    changed: bool,
+}
+
+impl Overrides {
+    /// Path of the overrides file of a config
+    ///
+    /// * `folder`: folder of the config
+    pub(crate) fn path(folder: &Path) -> PathBuf {
+        folder.join(OVERRIDES_FILE_NAME)
+    }
+
+    /// Load the overrides next to a config, empty if there is no overrides file
+    ///
+    /// * `folder`: folder of the config
+    pub(crate) fn load(folder: &Path) -> Result<Overrides, MentalError> {
+        let path = Overrides::path(folder);
+        match fs::read_to_string(&path) {
+            Ok(content) => serde_yaml::from_str(&content).map_err(|e| MentalError::yaml(&path, e)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(Overrides::default()),
+            Err(error) => Err(MentalError::io(&path, error)),
+        }
+    }
+
+    /// Values overriding a component
+    ///
+    /// * `component`: name of the component
+    pub(crate) fn values(&self, component: &str) -> impl Iterator<Item = (&String, &Value)> {
+        self.values.get(component).into_iter().flatten()
+    }
+
+    /// Override a value
+    ///
+    /// * `component`: name of the component
+    /// * `key`: name of the value without the prefix
+    /// * `value`: new value
+    pub(crate) fn set(&mut self, component: &str, key: &str, value: Value) {
+        self.values
+            .entry(component.to_string())
+            .or_default()
+            .insert(key.to_string(), value);
+        self.changed = true;
+    }
+
+    /// Remove the override of a value
+    ///
+    /// * `component`: name of the component
+    /// * `key`: name of the value without the prefix
+    pub(crate) fn unset(&mut self, component: &str, key: &str) {
+        if let Some(values) = self.values.get_mut(component) {
+            self.changed |= values.remove(key).is_some();
+            if values.is_empty() {
+                self.values.remove(component);
+            }
+        }
+    }
+
+    /// Remove the overrides of a component
+    ///
+    /// * `component`: name of the component
+    pub(crate) fn remove_component(&mut self, component: &str) {
+        self.changed |= self.values.remove(component).is_some();
+    }
+
+    /// Move the overrides of a component to a new name
+    ///
+    /// * `old`: current name of the component
+    /// * `new`: new name of the component
+    pub(crate) fn rename_component(&mut self, old: &str, new: &str) {
+        if let Some(values) = self.values.remove(old) {
+            self.values.insert(new.to_string(), values);
+            self.changed = true;
+        }
+    }
+
+    /// Give a copy of a component the overrides of its source
+    ///
+    /// * `source`: name of the copied component
+    /// * `destination`: name of the copy
+    pub(crate) fn copy_component(&mut self, source: &str, destination: &str) {
+        if let Some(values) = self.values.get(source).cloned() {
+            self.values.insert(destination.to_string(), values);
+            self.changed = true;
+        }
+    }
+
+    /// Check if the values differ from the overrides file
+    pub(crate) fn is_changed(&self) -> bool {
+        self.changed
+    }
+
+    /// Write the overrides next to a config
+    ///
+    /// The file is only readable by the owner as it may hold secrets in plain text. It is
+    /// added to the `.gitignore` of the folder, which is created if there is none.
+    ///
+    /// * `folder`: folder of the config
+    pub(crate) fn save(&self, folder: &Path) -> Result<PathBuf, MentalError> {
+        let path = Overrides::path(folder);
+        let content = serde_yaml::to_string(self).map_err(|e| {
+            MentalError::Validation(format!("could not serialize {}: {}", path.display(), e))
+        })?;
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let mut file = options.open(&path).map_err(|e| MentalError::io(&path, e))?;
+        // the mode only applies to new files
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(fs::Permissions::from_mode(0o600))
+                .map_err(|e| MentalError::io(&path, e))?;
+        }
+        file.write_all(content.as_bytes())
+            .map_err(|e| MentalError::io(&path, e))?;
+
+        util::ignore_in_git(&path)?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Create an empty folder for a test
+    ///
+    /// * `name`: name of the test
+    fn folder(name: &str) -> PathBuf {
+        let folder = std::env::temp_dir().join(format!("mental-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&folder);
+        fs::create_dir_all(&folder).unwrap();
+        folder
+    }
+
+    #[test]
+    fn saved_overrides_load_again() {
+        let folder = folder("overrides-load");
+        assert!(Overrides::load(&folder).unwrap().values.is_empty());
+
+        let mut overrides = Overrides::default();
+        overrides.set("db", "PASSWORD", Value::String("it's $ecret".into()));
+        overrides.set("db", "PORT", Value::Integer(5432));
+        overrides.save(&folder).unwrap();
+
+        let loaded = Overrides::load(&folder).unwrap();
+        let values: Vec<(&String, &Value)> = loaded.values("db").collect();
+        assert_eq!(values.len(), 2);
+        assert_eq!(values[0].1, &Value::String("it's $ecret".into()));
+        assert_eq!(values[1].1, &Value::Integer(5432));
+        assert_eq!(loaded.values("web").count(), 0);
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[test]
+    fn overrides_are_ignored_by_git() {
+        let folder = folder("overrides-gitignore");
+        Overrides::default().save(&folder).unwrap();
+        assert_eq!(
+            fs::read_to_string(folder.join(".gitignore")).unwrap(),
+            ".mental.local.yaml\n"
+        );
+
+        fs::write(folder.join(".gitignore"), "target").unwrap();
+        Overrides::default().save(&folder).unwrap();
+        Overrides::default().save(&folder).unwrap();
+        assert_eq!(
+            fs::read_to_string(folder.join(".gitignore")).unwrap(),
+            "target\n.mental.local.yaml\n"
+        );
+        fs::remove_dir_all(&folder).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn existing_overrides_become_private() {
+        use std::os::unix::fs::PermissionsExt;
+        let folder = folder("overrides-mode");
+        let path = Overrides::path(&folder);
+        fs::write(&path, "{}").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        Overrides::default().save(&folder).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        fs::remove_dir_all(&folder).unwrap();
+    }
+}
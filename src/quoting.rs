@@ -0,0 +1,161 @@
+//! Quoting and escaping of values written to .env files
+
+use clap::ValueEnum;
+
+/// Dialect used to quote string values
+///
+/// Consumers of .env files disagree on how quotes and escapes are interpreted, so the
+/// dialect has to match the tool that reads the file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum Quoting {
+    /// docker compose: double quotes, escapes `\`, `"`, `$` and newlines
+    #[default]
+    Compose,
+    /// python-dotenv: double quotes escaping `\`, `"` and newlines, values containing `$`
+    /// are single quoted as python-dotenv does not expand them
+    #[value(name = "python-dotenv")]
+    PythonDotenv,
+    /// POSIX shell: single quotes, embedded single quotes are written as `'\''`
+    Posix,
+    /// single quoted literal without any escaping
+    Literal,
+}
+
+/// Escape characters inside a double quoted value
+///
+/// * `value`: raw value
+/// * `escape_dollar`: if `$` has to be escaped to prevent interpolation
+fn escape_double_quoted(value: &str, escape_dollar: bool) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str(r"\\"),
+            '"' => escaped.push_str(r#"\""#),
+            '\n' => escaped.push_str(r"\n"),
+            '\r' => escaped.push_str(r"\r"),
+            '\t' => escaped.push_str(r"\t"),
+            '$' if escape_dollar => escaped.push_str(r"\$"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+impl Quoting {
+    /// Quote a string value for the dialect
+    ///
+    /// Returns `None` if the value can not be represented in the dialect.
+    ///
+    /// * `value`: raw value
+    pub(crate) fn quote(&self, value: &str) -> Option<String> {
+        match self {
+            Quoting::Compose => Some(format!(r#""{}""#, escape_double_quoted(value, true))),
+            Quoting::PythonDotenv if value.contains('$') => Some(format!(
+                "'{}'",
+                value.replace('\\', r"\\").replace('\'', r"\'")
+            )),
+            Quoting::PythonDotenv => Some(format!(r#""{}""#, escape_double_quoted(value, false))),
+            Quoting::Posix => Some(format!("'{}'", value.replace('\'', r"'\''"))),
+            Quoting::Literal => match value.contains('\'') {
+                true => None,
+                false => Some(format!("'{}'", value)),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dotenv;
+
+    const VALUES: [&str; 8] = [
+        "plain",
+        r#"say "hi""#,
+        r"back\slash",
+        "$HOME and ${VAR}",
+        "it's",
+        "multi\nline",
+        "tab\tand\rreturn",
+        "p@$$'w\"\n\\d",
+    ];
+
+    /// Read a value back the way the shell like parser of `import` does
+    fn parse(quoted: &str) -> String {
+        let parsed = dotenv::parse(&format!("KEY={}\n", quoted)).unwrap();
+        assert_eq!(parsed.len(), 1, "{}", quoted);
+        parsed[0].1.clone()
+    }
+
+    /// Read a value back the way python-dotenv does
+    fn parse_python_dotenv(quoted: &str) -> String {
+        let (quote, inner) = match quoted.chars().next() {
+            Some(quote @ ('\'' | '"')) => (quote, &quoted[1..quoted.len() - 1]),
+            _ => panic!("python-dotenv value is not quoted: {}", quoted),
+        };
+        let mut value = String::new();
+        let mut chars = inner.chars();
+        while let Some(c) = chars.next() {
+            if c != '\\' {
+                assert!(c != quote, "unescaped quote in {}", quoted);
+                value.push(c);
+                continue;
+            }
+            let escaped = chars.next().unwrap();
+            match (quote, escaped) {
+                ('\'', '\\' | '\'') => value.push(escaped),
+                ('"', '\\' | '\'' | '"') => value.push(escaped),
+                ('"', 'n') => value.push('\n'),
+                ('"', 'r') => value.push('\r'),
+                ('"', 't') => value.push('\t'),
+                _ => {
+                    value.push('\\');
+                    value.push(escaped);
+                }
+            }
+        }
+        value
+    }
+
+    #[test]
+    fn compose_round_trips() {
+        for value in VALUES {
+            assert_eq!(parse(&Quoting::Compose.quote(value).unwrap()), value);
+        }
+    }
+
+    #[test]
+    fn posix_round_trips() {
+        for value in VALUES {
+            assert_eq!(parse(&Quoting::Posix.quote(value).unwrap()), value);
+        }
+    }
+
+    #[test]
+    fn literal_round_trips_or_refuses() {
+        for value in VALUES {
+            match Quoting::Literal.quote(value) {
+                Some(quoted) => assert_eq!(parse(&quoted), value),
+                None => assert!(value.contains('\'')),
+            }
+        }
+    }
+
+    #[test]
+    fn python_dotenv_round_trips() {
+        for value in VALUES {
+            let quoted = Quoting::PythonDotenv.quote(value).unwrap();
+            assert_eq!(parse_python_dotenv(&quoted), value);
+        }
+    }
+
+    #[test]
+    fn python_dotenv_does_not_expand_dollars() {
+        for value in VALUES.iter().filter(|v| v.contains('$')) {
+            assert!(Quoting::PythonDotenv
+                .quote(value)
+                .unwrap()
+                .starts_with('\''));
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! Values read from files, the environment or the output of commands
+
+use crate::error::MentalError;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+use wait_timeout::ChildExt;
+
+/// Seconds a command may run if the reference does not set a timeout
+const DEFAULT_TIMEOUT: u64 = 10;
+
+/// Value resolved from a source outside of the config when it is rendered
+///
+/// Trailing line breaks of files and command output are removed. Maps with other keys are
+/// no reference, so plain objects holding a `file`, `env` or `cmd` key stay objects.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize, JsonSchema)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum Reference {
+    /// content of a file, relative paths start at the folder of the config
+    File { file: PathBuf },
+    /// variable of the environment `mental` runs in
+    Env { env: String },
+    /// standard output of a shell command
+    Cmd {
+        cmd: String,
+        /// seconds the command may run, defaults to 10
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        timeout: Option<u64>,
+    },
+}
+
+impl fmt::Display for Reference {
+    /// Format the reference the way it is written in the config
+    ///
+    /// * `f`: formatter
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reference::File { file } => write!(f, "file:{}", file.display()),
+            Reference::Env { env } => write!(f, "env:{}", env),
+            Reference::Cmd { cmd, .. } => write!(f, "cmd:{}", cmd),
+        }
+    }
+}
+
+impl Reference {
+    /// Read the value the reference points to
+    ///
+    /// * `name`: name of the variable holding the reference, used in errors
+    /// * `base`: folder relative file paths start at
+    pub(crate) fn resolve(&self, name: &str, base: &Path) -> Result<String, MentalError> {
+        let value = match self {
+            Reference::File { file } => {
+                let path = base.join(file);
+                fs::read_to_string(&path).map_err(|e| MentalError::io(&path, e))?
+            }
+            Reference::Env { env } => env::var(env).map_err(|e| {
+                MentalError::Validation(format!(
+                    "environment variable '{}' referenced by '{}' {}",
+                    env,
+                    name,
+                    match e {
+                        env::VarError::NotPresent => "is not set",
+                        env::VarError::NotUnicode(_) => "is not valid UTF-8",
+                    }
+                ))
+            })?,
+            Reference::Cmd { cmd, timeout } => run(
+                cmd,
+                name,
+                base,
+                Duration::from_secs(timeout.unwrap_or(DEFAULT_TIMEOUT)),
+            )?,
+        };
+        Ok(value.trim_end_matches(['\n', '\r']).to_string())
+    }
+}
+
+/// Capture the standard output of a shell command
+///
+/// The command inherits standard input, so tools asking for a password keep working.
+///
+/// * `cmd`: command passed to the shell
+/// * `name`: name of the variable holding the reference, used in errors
+/// * `base`: folder the command runs in
+/// * `timeout`: time after which the command is killed
+fn run(cmd: &str, name: &str, base: &Path, timeout: Duration) -> Result<String, MentalError> {
+    let mut command = match cfg!(windows) {
+        true => {
+            let mut command = Command::new("cmd");
+            command.arg("/C");
+            command
+        }
+        false => {
+            let mut command = Command::new("sh");
+            command.arg("-c");
+            command
+        }
+    };
+    if !base.as_os_str().is_empty() {
+        command.current_dir(base);
+    }
+    let mut child = command
+        .arg(cmd)
+        .stdin(Stdio::inherit())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| MentalError::io(Path::new(cmd), e))?;
+
+    // read both pipes while waiting, a full pipe would block the command
+    let mut stdout = child.stdout.take();
+    let mut stderr = child.stderr.take();
+    let stdout = thread::spawn(move || {
+        let mut output = Vec::new();
+        stdout.as_mut().map(|s| s.read_to_end(&mut output));
+        output
+    });
+    let stderr = thread::spawn(move || {
+        let mut output = Vec::new();
+        stderr.as_mut().map(|s| s.read_to_end(&mut output));
+        output
+    });
+
+    let status = match child
+        .wait_timeout(timeout)
+        .map_err(|e| MentalError::io(Path::new(cmd), e))?
+    {
+        Some(status) => status,
+        None => {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Err(MentalError::Validation(format!(
+                "command '{}' of '{}' timed out after {}s",
+                cmd,
+                name,
+                timeout.as_secs()
+            )));
+        }
+    };
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+    if !status.success() {
+        let reason = match status.code() {
+            Some(code) => format!("exit code {}", code),
+            None => "a signal".into(),
+        };
+        return Err(MentalError::Validation(format!(
+            "command '{}' of '{}' failed with {}: {}",
+            cmd,
+            name,
+            reason,
+            String::from_utf8_lossy(&stderr).trim()
+        )));
+    }
+    String::from_utf8(stdout).map_err(|_| {
+        MentalError::Validation(format!(
+            "output of command '{}' of '{}' is not valid UTF-8",
+            cmd, name
+        ))
+    })
+}
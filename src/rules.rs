@@ -0,0 +1,174 @@
+//! Rules the values of variables are checked against
+
+use crate::components::Value;
+use regex::Regex;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Rules a value has to follow, checked against the value after it is resolved
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Rules {
+    /// regular expression the whole value has to match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pattern: Option<String>,
+    /// values that are allowed
+    #[serde(default, rename = "enum", skip_serializing_if = "Vec::is_empty")]
+    allowed: Vec<Value>,
+    /// smallest number the value may be
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    min: Option<f64>,
+    /// largest number the value may be
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    max: Option<f64>,
+    /// the value has to be a URL with a scheme and a host, like `https://example.com`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    url: bool,
+    /// the value has to be a port between 1 and 65535
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    port: bool,
+}
+
+impl Rules {
+    /// Check a value against the rules
+    ///
+    /// Returns a description of each broken rule.
+    ///
+    /// * `value`: value as it is put into the environment
+    pub(crate) fn check(&self, value: &str) -> Vec<String> {
+        let mut broken: Vec<String> = Vec::new();
+        if let Some(pattern) = &self.pattern {
+            match compile(pattern) {
+                Ok(regex) if regex.is_match(value) => {}
+                Ok(_) => broken.push(format!("does not match pattern '{}'", pattern)),
+                Err(error) => broken.push(format!("has an invalid pattern: {}", error)),
+            }
+        }
+        if !self.allowed.is_empty() {
+            let allowed: Vec<String> = self.allowed.iter().map(|v| v.to_raw_string(None)).collect();
+            if !allowed.iter().any(|a| a == value) {
+                broken.push(format!("is not one of {}", allowed.join(", ")));
+            }
+        }
+        if self.min.is_some() || self.max.is_some() {
+            match value.trim().parse::<f64>() {
+                Ok(number) => {
+                    if let Some(min) = self.min.filter(|min| number < *min) {
+                        broken.push(format!("is less than {}", min));
+                    }
+                    if let Some(max) = self.max.filter(|max| number > *max) {
+                        broken.push(format!("is greater than {}", max));
+                    }
+                }
+                Err(_) => broken.push("is not a number".into()),
+            }
+        }
+        if self.url && !is_url(value) {
+            broken.push("is not a URL".into());
+        }
+        if self.port && !value.parse::<u16>().is_ok_and(|port| port > 0) {
+            broken.push("is not a port".into());
+        }
+        broken
+    }
+
+    /// Describe why the pattern does not compile, `None` if it compiles or there is none
+    pub(crate) fn pattern_error(&self) -> Option<String> {
+        compile(self.pattern.as_ref()?).err().map(|e| e.to_string())
+    }
+}
+
+/// Compile a pattern matching the whole value
+///
+/// * `pattern`: regular expression
+fn compile(pattern: &str) -> Result<Regex, regex::Error> {
+    Regex::new(&format!("^(?:{})$", pattern))
+}
+
+/// Check if a value looks like a URL with a scheme and a host
+///
+/// * `value`: value to check
+fn is_url(value: &str) -> bool {
+    let (scheme, rest) = match value.split_once("://") {
+        Some(parts) => parts,
+        None => return false,
+    };
+    let mut scheme_chars = scheme.chars();
+    let valid_scheme = scheme_chars.next().is_some_and(|c| c.is_ascii_alphabetic())
+        && scheme_chars.all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c));
+    let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    valid_scheme && !host.is_empty() && !value.chars().any(char::is_whitespace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse rules from YAML
+    ///
+    /// * `yaml`: rules as written in the config
+    fn parse(yaml: &str) -> Rules {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    #[test]
+    fn pattern_matches_the_whole_value() {
+        let rules = parse("pattern: '[a-z]+'");
+        assert!(rules.check("abc").is_empty());
+        assert_eq!(rules.check("abc1"), vec!["does not match pattern '[a-z]+'"]);
+        assert!(rules.pattern_error().is_none());
+    }
+
+    #[test]
+    fn invalid_patterns_are_reported() {
+        let rules = parse("pattern: '[a-z'");
+        assert!(rules.pattern_error().is_some());
+        assert!(rules.check("abc")[0].starts_with("has an invalid pattern"));
+        assert!(Rules::default().pattern_error().is_none());
+    }
+
+    #[test]
+    fn enum_compares_the_raw_values() {
+        let rules = parse("enum: [debug, 1, true]");
+        assert!(rules.check("debug").is_empty());
+        assert!(rules.check("1").is_empty());
+        assert!(rules.check("true").is_empty());
+        assert_eq!(rules.check("info"), vec!["is not one of debug, 1, true"]);
+    }
+
+    #[test]
+    fn numbers_are_checked_against_the_bounds() {
+        let rules = parse("{min: 1, max: 10.5}");
+        assert!(rules.check("1").is_empty());
+        assert!(rules.check(" 10.5 ").is_empty());
+        assert_eq!(rules.check("0"), vec!["is less than 1"]);
+        assert_eq!(rules.check("11"), vec!["is greater than 10.5"]);
+        assert_eq!(rules.check("ten"), vec!["is not a number"]);
+    }
+
+    #[test]
+    fn urls_and_ports() {
+        let rules = parse("{url: true}");
+        assert!(rules.check("https://example.com/path?q=1").is_empty());
+        assert!(rules.check("postgres+ssl://db:5432").is_empty());
+        for url in [
+            "example.com",
+            "https://",
+            "1http://host",
+            "https://host/a b",
+        ] {
+            assert_eq!(rules.check(url), vec!["is not a URL"], "{}", url);
+        }
+
+        let rules = parse("{port: true}");
+        assert!(rules.check("8080").is_empty());
+        for port in ["0", "65536", "-1", "http"] {
+            assert_eq!(rules.check(port), vec!["is not a port"], "{}", port);
+        }
+    }
+
+    #[test]
+    fn unknown_rules_are_rejected() {
+        assert!(serde_yaml::from_str::<Rules>("{length: 3}").is_err());
+    }
+}
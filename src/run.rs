@@ -0,0 +1,112 @@
+//! Run commands with the variables of components in their environment
+
+use crate::error::MentalError;
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+/// Build a command with the variables added to its environment
+///
+/// * `command`: program followed by its arguments
+/// * `variables`: variables put into the environment of the command
+/// * `keep_existing`: keep variables that are already set instead of overriding them
+fn build(
+    command: &[String],
+    variables: &[(String, String)],
+    keep_existing: bool,
+) -> Result<Command, MentalError> {
+    let (program, args) = match command.split_first() {
+        Some(split) => split,
+        None => return Err(MentalError::Validation("no command given".into())),
+    };
+    let mut child = Command::new(program);
+    child.args(args);
+    for (name, value) in variables {
+        if keep_existing && env::var_os(name).is_some() {
+            continue;
+        }
+        child.env(name, value);
+    }
+    Ok(child)
+}
+
+/// Run a command with the variables added to its environment
+///
+/// On Unix the cli process is replaced by the command, so signals reach the command
+/// directly and its exit code becomes the exit code of the cli. On other platforms the
+/// command is spawned and its exit code is forwarded.
+///
+/// * `command`: program followed by its arguments
+/// * `variables`: variables put into the environment of the command
+/// * `keep_existing`: keep variables that are already set instead of overriding them
+pub(crate) fn exec(
+    command: &[String],
+    variables: &[(String, String)],
+    keep_existing: bool,
+) -> Result<(), MentalError> {
+    let mut child = build(command, variables, keep_existing)?;
+    let program = child.get_program().to_os_string();
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+
+        // only returns if the command could not be started
+        let error = child.exec();
+        Err(MentalError::io(Path::new(&program), error))
+    }
+
+    #[cfg(not(unix))]
+    {
+        let status = child
+            .status()
+            .map_err(|e| MentalError::io(Path::new(&program), e))?;
+        std::process::exit(status.code().unwrap_or(1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::OsStr;
+
+    /// Turn string slices into owned strings
+    ///
+    /// * `values`: strings to convert
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|v| v.to_string()).collect()
+    }
+
+    #[test]
+    fn commands_get_the_variables() {
+        let variables = vec![("MENTAL_RUN_TEST".to_string(), "1".to_string())];
+        let command = build(&strings(&["echo", "a", "b"]), &variables, false).unwrap();
+        assert_eq!(command.get_program(), "echo");
+        assert_eq!(command.get_args().collect::<Vec<_>>(), ["a", "b"]);
+        assert_eq!(
+            command.get_envs().collect::<Vec<_>>(),
+            [(OsStr::new("MENTAL_RUN_TEST"), Some(OsStr::new("1")))]
+        );
+    }
+
+    #[test]
+    fn existing_variables_can_be_kept() {
+        let variables = vec![
+            ("PATH".to_string(), "/nowhere".to_string()),
+            ("MENTAL_RUN_UNSET".to_string(), "1".to_string()),
+        ];
+        let kept = build(&strings(&["true"]), &variables, true).unwrap();
+        assert_eq!(
+            kept.get_envs().collect::<Vec<_>>(),
+            [(OsStr::new("MENTAL_RUN_UNSET"), Some(OsStr::new("1")))]
+        );
+        let overridden = build(&strings(&["true"]), &variables, false).unwrap();
+        assert_eq!(overridden.get_envs().count(), 2);
+    }
+
+    #[test]
+    fn a_command_is_required() {
+        let error = build(&[], &[], false).unwrap_err();
+        assert!(matches!(error, MentalError::Validation(_)));
+    }
+}
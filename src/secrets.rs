@@ -0,0 +1,311 @@
+//! Encryption of secret values stored in the config
+
+use crate::error::MentalError;
+use crate::util;
+use argon2::Argon2;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chacha20poly1305::aead::rand_core::RngCore;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::cell::{OnceCell, RefCell};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Name of the key file searched next to the config
+const KEY_FILE_NAME: &str = ".mental.key";
+/// Variable pointing to a key file outside of the folder of the config
+const KEY_FILE_VARIABLE: &str = "MENTAL_KEY_FILE";
+/// Length of the salt the key of a secret is derived with
+const SALT_LENGTH: usize = 16;
+/// Length of the nonce of a secret
+const NONCE_LENGTH: usize = 24;
+
+/// Salt the key of a secret is derived with
+type Salt = [u8; SALT_LENGTH];
+
+/// Encrypted value stored in the config
+///
+/// * `secret`: base64 of the salt, the nonce and the ciphertext
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct Secret {
+    secret: String,
+}
+
+impl Secret {
+    /// Encrypted value as stored in the config
+    pub(crate) fn encoded(&self) -> &str {
+        &self.secret
+    }
+
+    /// Salt the key of the secret was derived with
+    pub(crate) fn salt(&self) -> Option<Salt> {
+        let bytes = STANDARD.decode(&self.secret).ok()?;
+        bytes.get(..SALT_LENGTH)?.try_into().ok()
+    }
+}
+
+/// Key material used to encrypt and decrypt secrets
+///
+/// The master secret is read from the key file, or prompted for as a passphrase if there is
+/// no key file, once the first secret is used. Keys are derived from it per salt with
+/// Argon2 and cached.
+///
+/// * `key_file`: file holding the master secret
+/// * `master`: master secret once it is loaded
+/// * `keys`: derived keys by salt
+#[derive(Default)]
+pub(crate) struct Keyring {
+    key_file: PathBuf,
+    master: OnceCell<Vec<u8>>,
+    keys: RefCell<HashMap<Salt, Key>>,
+}
+
+impl fmt::Debug for Keyring {
+    /// Format the keyring without exposing key material
+    ///
+    /// * `f`: formatter
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Keyring")
+            .field("key_file", &self.key_file)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Keyring {
+    /// Create the keyring of a config
+    ///
+    /// The key file is taken from `MENTAL_KEY_FILE` and defaults to `.mental.key` next to
+    /// the config.
+    ///
+    /// * `config_file`: path of the config
+    pub(crate) fn new(config_file: &Path) -> Keyring {
+        let key_file = match std::env::var_os(KEY_FILE_VARIABLE) {
+            Some(path) => PathBuf::from(path),
+            None => config_file
+                .parent()
+                .unwrap_or(Path::new(""))
+                .join(KEY_FILE_NAME),
+        };
+        Keyring {
+            key_file,
+            ..Keyring::default()
+        }
+    }
+
+    /// Create a keyring with a known master secret
+    ///
+    /// * `key_file`: file the master secret is stored in
+    /// * `master`: master secret
+    pub(crate) fn with_master(key_file: &Path, master: Vec<u8>) -> Keyring {
+        Keyring {
+            key_file: key_file.to_path_buf(),
+            master: OnceCell::from(master),
+            keys: RefCell::default(),
+        }
+    }
+
+    /// File the master secret is read from
+    pub(crate) fn key_file(&self) -> &Path {
+        &self.key_file
+    }
+
+    /// Load the master secret
+    ///
+    /// * `message`: prompt shown if there is no key file
+    /// * `confirm`: ask twice if the passphrase is prompted for, used for new passphrases
+    fn master(&self, message: &str, confirm: bool) -> Result<&[u8], MentalError> {
+        if let Some(master) = self.master.get() {
+            return Ok(master);
+        }
+        let master = match fs::read(&self.key_file) {
+            Ok(content) => content.trim_ascii().to_vec(),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                prompt_passphrase(message, confirm)?
+            }
+            Err(error) => return Err(MentalError::io(&self.key_file, error)),
+        };
+        if master.is_empty() {
+            return Err(MentalError::Validation(format!(
+                "key file {} is empty",
+                self.key_file.display()
+            )));
+        }
+        Ok(self.master.get_or_init(|| master))
+    }
+
+    /// Load the master secret before it is replaced, prompting for the current passphrase
+    /// if there is no key file
+    pub(crate) fn unlock_current(&self) -> Result<(), MentalError> {
+        self.master("Current passphrase of the secrets:", false)
+            .map(|_| ())
+    }
+
+    /// Derive the key for a salt
+    ///
+    /// * `salt`: salt of the secret
+    /// * `confirm`: ask twice if the passphrase is prompted for
+    fn key(&self, salt: &Salt, confirm: bool) -> Result<Key, MentalError> {
+        if let Some(key) = self.keys.borrow().get(salt) {
+            return Ok(*key);
+        }
+        let mut key = Key::default();
+        Argon2::default()
+            .hash_password_into(
+                self.master("Passphrase of the secrets:", confirm)?,
+                salt,
+                &mut key,
+            )
+            .map_err(|e| MentalError::Validation(format!("could not derive key: {}", e)))?;
+        self.keys.borrow_mut().insert(*salt, key);
+        Ok(key)
+    }
+
+    /// Encrypt a value
+    ///
+    /// Secrets of a config share a salt so the key is only derived once, a new salt is
+    /// generated if `salt` is `None`. A passphrase prompted for a new salt is asked twice.
+    ///
+    /// * `value`: plain value
+    /// * `salt`: salt of the existing secrets
+    pub(crate) fn encrypt(&self, value: &str, salt: Option<Salt>) -> Result<Secret, MentalError> {
+        let is_new = salt.is_none();
+        let salt = salt.unwrap_or_else(|| {
+            let mut salt = Salt::default();
+            OsRng.fill_bytes(&mut salt);
+            salt
+        });
+        let key = self.key(&salt, is_new)?;
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = XChaCha20Poly1305::new(&key)
+            .encrypt(&nonce, value.as_bytes())
+            .map_err(|_| MentalError::Validation("could not encrypt secret".into()))?;
+        let mut bytes = Vec::with_capacity(SALT_LENGTH + NONCE_LENGTH + ciphertext.len());
+        bytes.extend_from_slice(&salt);
+        bytes.extend_from_slice(&nonce);
+        bytes.extend_from_slice(&ciphertext);
+        Ok(Secret {
+            secret: STANDARD.encode(bytes),
+        })
+    }
+
+    /// Decrypt a secret
+    ///
+    /// * `secret`: encrypted value
+    pub(crate) fn decrypt(&self, secret: &Secret) -> Result<String, MentalError> {
+        let bytes = STANDARD
+            .decode(&secret.secret)
+            .map_err(|e| MentalError::Validation(format!("secret is not valid base64: {}", e)))?;
+        if bytes.len() < SALT_LENGTH + NONCE_LENGTH {
+            return Err(MentalError::Validation("secret is too short".into()));
+        }
+        let (salt, rest) = bytes.split_at(SALT_LENGTH);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LENGTH);
+        let key = self.key(salt.try_into().unwrap_or(&Salt::default()), false)?;
+        let plaintext = XChaCha20Poly1305::new(&key)
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| {
+                MentalError::Validation(format!(
+                    "could not decrypt secret, the key does not match (key file {})",
+                    self.key_file.display()
+                ))
+            })?;
+        String::from_utf8(plaintext)
+            .map_err(|_| MentalError::Validation("secret is not valid UTF-8".into()))
+    }
+}
+
+/// Prompt for the passphrase the keys are derived from
+///
+/// * `message`: prompt telling which passphrase is asked for
+/// * `confirm`: ask twice, used for new passphrases
+pub(crate) fn prompt_passphrase(message: &str, confirm: bool) -> Result<Vec<u8>, MentalError> {
+    let prompt = inquire::Password::new(message);
+    let prompt = match confirm {
+        true => prompt.with_custom_confirmation_message("Repeat the passphrase:"),
+        false => prompt.without_confirmation(),
+    };
+    Ok(prompt.prompt()?.into_bytes())
+}
+
+/// Generate the content of a new key file
+pub(crate) fn generate_key() -> String {
+    let mut key = [0u8; 32];
+    OsRng.fill_bytes(&mut key);
+    STANDARD.encode(key)
+}
+
+/// Add a key file next to the config to the `.gitignore` of its folder
+///
+/// Key files set with `MENTAL_KEY_FILE` are kept outside of the project and left alone.
+///
+/// * `key_file`: path of the key file
+pub(crate) fn ignore_key_file(key_file: &Path) -> Result<(), MentalError> {
+    match std::env::var_os(KEY_FILE_VARIABLE) {
+        Some(_) => Ok(()),
+        None => util::ignore_in_git(key_file),
+    }
+}
+
+/// Write a key file readable only by the owner
+///
+/// * `path`: path of the key file
+/// * `content`: master secret
+pub(crate) fn write_key_file(path: &Path, content: &str) -> Result<(), MentalError> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(|e| MentalError::io(path, e))?;
+    writeln!(file, "{}", content).map_err(|e| MentalError::io(path, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Keyring with a master secret that is not read from a file
+    ///
+    /// * `master`: master secret
+    fn keyring(master: &str) -> Keyring {
+        Keyring::with_master(Path::new(".mental.key"), master.as_bytes().to_vec())
+    }
+
+    #[test]
+    fn encrypted_values_decrypt_again() {
+        let keyring = keyring("first key");
+        let secret = keyring.encrypt("hunter2\nwith 'quotes'", None).unwrap();
+        assert!(!secret.encoded().contains("hunter2"));
+        assert_eq!(keyring.decrypt(&secret).unwrap(), "hunter2\nwith 'quotes'");
+
+        // secrets sharing a salt still get their own nonce
+        let other = keyring
+            .encrypt("hunter2\nwith 'quotes'", secret.salt())
+            .unwrap();
+        assert_eq!(other.salt(), secret.salt());
+        assert_ne!(other.encoded(), secret.encoded());
+        assert_eq!(keyring.decrypt(&other).unwrap(), "hunter2\nwith 'quotes'");
+    }
+
+    #[test]
+    fn wrong_keys_do_not_decrypt() {
+        let secret = keyring("first key").encrypt("hunter2", None).unwrap();
+        let error = keyring("second key").decrypt(&secret).unwrap_err();
+        assert!(matches!(error, MentalError::Validation(_)));
+        assert!(error.to_string().contains("the key does not match"));
+
+        let broken = Secret {
+            secret: "bm90IGEgc2VjcmV0".into(),
+        };
+        assert!(keyring("first key").decrypt(&broken).is_err());
+    }
+}
@@ -1,27 +1,195 @@
 //! Util functions
 
+use crate::error::MentalError;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use ignore::WalkBuilder;
+use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
-use std::{fs, io};
 
-/// Filter for folders under a given path
+/// Options of the search for target folders
 ///
-/// * `path`: path to search in
-pub(crate) fn folders(path: &Path) -> Result<Vec<PathBuf>, io::Error> {
-    Ok(fs::read_dir(path)?
-        .filter_map(|entry| Some(entry.ok()?.path().to_path_buf()))
-        .filter(|path| path.is_dir())
-        .collect())
+/// * `depth`: how many levels below the searched folder are listed
+/// * `include`: globs a folder has to match, relative to the searched folder
+/// * `exclude`: globs of folders that are skipped together with their subfolders
+/// * `markers`: files one of which has to exist in a folder, e.g. `Cargo.toml`
+#[derive(Debug, Clone)]
+pub(crate) struct Discovery {
+    pub(crate) depth: usize,
+    pub(crate) include: Vec<String>,
+    pub(crate) exclude: Vec<String>,
+    pub(crate) markers: Vec<String>,
 }
 
-/// List all folders for a given path
+impl Default for Discovery {
+    /// Only list the direct children of the searched folder
+    fn default() -> Discovery {
+        Discovery {
+            depth: 1,
+            include: Vec::new(),
+            exclude: Vec::new(),
+            markers: Vec::new(),
+        }
+    }
+}
+
+/// Compile globs into a single matcher
+///
+/// * `globs`: glob patterns
+fn glob_set(globs: &[String]) -> Result<GlobSet, MentalError> {
+    let mut builder = GlobSetBuilder::new();
+    for glob in globs {
+        let glob = Glob::new(glob)
+            .map_err(|e| MentalError::Validation(format!("invalid glob '{}': {}", glob, e)))?;
+        builder.add(glob);
+    }
+    builder
+        .build()
+        .map_err(|e| MentalError::Validation(format!("invalid globs: {}", e)))
+}
+
+/// List the folder and the folders below it
+///
+/// Hidden folders and folders ignored by `.gitignore` or `.ignore` files are skipped.
+///
+/// * `folder`: folder to search in
+/// * `discovery`: depth, globs and marker files the folders are filtered with
+pub(crate) fn list_folders(
+    folder: &Path,
+    discovery: &Discovery,
+) -> Result<Vec<PathBuf>, MentalError> {
+    let include = glob_set(&discovery.include)?;
+    let exclude = glob_set(&discovery.exclude)?;
+    let root = folder.to_path_buf();
+    let relative = move |path: &Path| path.strip_prefix(&root).unwrap_or(path).to_path_buf();
+    let skip = relative.clone();
+
+    let walker = WalkBuilder::new(folder)
+        .max_depth(Some(discovery.depth))
+        .require_git(false)
+        .filter_entry(move |entry| {
+            let path = skip(entry.path());
+            path.as_os_str().is_empty() || !exclude.is_match(&path)
+        })
+        .sort_by_file_path(|a, b| a.cmp(b))
+        .build();
+
+    let mut folders: Vec<PathBuf> = Vec::new();
+    for entry in walker {
+        let entry = entry.map_err(|e| MentalError::io(folder, io::Error::other(e)))?;
+        if !entry.file_type().is_some_and(|t| t.is_dir()) {
+            continue;
+        }
+        let path = relative(entry.path());
+        if !include.is_empty() && !include.is_match(&path) {
+            continue;
+        }
+        if !discovery.markers.is_empty()
+            && !discovery
+                .markers
+                .iter()
+                .any(|m| entry.path().join(m).is_file())
+        {
+            continue;
+        }
+        folders.push(entry.into_path());
+    }
+    Ok(folders)
+}
+
+/// List the mapping files (`*.map`) inside a folder
+///
+/// * `folder`: folder to search in
+pub(crate) fn mapping_files(folder: &Path) -> Result<Vec<PathBuf>, MentalError> {
+    let entries = fs::read_dir(folder).map_err(|e| MentalError::io(folder, e))?;
+    let mut files: Vec<PathBuf> = entries
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.is_file() && path.extension().is_some_and(|e| e == "map"))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+/// Add a file to the `.gitignore` of its folder, creating the `.gitignore` if needed
 ///
-/// * `folder`: folders to search in
-pub(crate) fn list_folders(folder: &Path) -> Vec<PathBuf> {
-    match folders(folder) {
-        Ok(mut folder_paths) => {
-            folder_paths.push(Path::new(folder).to_path_buf());
-            folder_paths
+/// * `file`: file that must not be committed
+pub(crate) fn ignore_in_git(file: &Path) -> Result<(), MentalError> {
+    let name = match file.file_name().and_then(|n| n.to_str()) {
+        Some(name) => name,
+        None => return Ok(()),
+    };
+    let gitignore = file.with_file_name(".gitignore");
+    let ignored = match fs::read_to_string(&gitignore) {
+        Ok(ignored) => ignored,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(error) => return Err(MentalError::io(&gitignore, error)),
+    };
+    if !ignored.lines().any(|l| l.trim() == name) {
+        let separator = match ignored.is_empty() || ignored.ends_with('\n') {
+            true => "",
+            false => "\n",
+        };
+        fs::write(&gitignore, format!("{}{}{}\n", ignored, separator, name))
+            .map_err(|e| MentalError::io(&gitignore, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// List the folders of a tree relative to its root
+    ///
+    /// * `root`: root of the tree
+    /// * `discovery`: options of the search
+    fn listed(root: &Path, discovery: Discovery) -> Vec<String> {
+        list_folders(root, &discovery)
+            .unwrap()
+            .iter()
+            .map(|f| f.strip_prefix(root).unwrap().to_string_lossy().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn folders_are_filtered_by_the_discovery() {
+        let root = std::env::temp_dir().join(format!("mental-folders-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        for folder in ["a/x", "b", ".hidden", "ignored"] {
+            fs::create_dir_all(root.join(folder)).unwrap();
         }
-        Err(error) => panic!("Problem opening the file: {:?}", error),
+        fs::write(root.join(".gitignore"), "ignored/\n").unwrap();
+        fs::write(root.join("b/Cargo.toml"), "").unwrap();
+
+        let deep = |depth| Discovery {
+            depth,
+            ..Discovery::default()
+        };
+        assert_eq!(listed(&root, Discovery::default()), ["", "a", "b"]);
+        assert_eq!(listed(&root, deep(2)), ["", "a", "a/x", "b"]);
+        let include = Discovery {
+            include: vec!["a/*".into()],
+            ..deep(2)
+        };
+        assert_eq!(listed(&root, include), ["a/x"]);
+        let exclude = Discovery {
+            exclude: vec!["a".into()],
+            ..deep(2)
+        };
+        assert_eq!(listed(&root, exclude), ["", "b"]);
+        let markers = Discovery {
+            markers: vec!["Cargo.toml".into()],
+            ..deep(2)
+        };
+        assert_eq!(listed(&root, markers), ["b"]);
+        let invalid = Discovery {
+            include: vec!["a[".into()],
+            ..Discovery::default()
+        };
+        assert!(matches!(
+            list_folders(&root, &invalid),
+            Err(MentalError::Validation(_))
+        ));
+        fs::remove_dir_all(&root).unwrap();
     }
 }
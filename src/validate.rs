@@ -0,0 +1,240 @@
+//! Checks of mappings against the config before they are applied
+
+use crate::config::MentalConfig;
+use crate::error::MentalError;
+use crate::format::OutputFormat;
+use crate::mapping::{same_target, MentalMapping};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Problem found in a mapping
+///
+/// * `target`: target path of the mapping
+/// * `message`: description of the problem
+#[derive(Debug)]
+pub(crate) struct Problem {
+    pub(crate) target: PathBuf,
+    pub(crate) message: String,
+}
+
+impl fmt::Display for Problem {
+    /// Format the problem prefixed with its target
+    ///
+    /// * `f`: formatter
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.target.display(), self.message)
+    }
+}
+
+/// Check the mappings of the given targets against the config
+///
+/// Reports requested targets without mapping, unknown components, targets that do not
+/// exist, targets mapped more than once into the same file, variables emitted by more than
+/// one component of a mapping, required values that are not set and values breaking their
+/// rules.
+///
+/// * `mapping`: mappings to check
+/// * `config`: config the components are taken from
+/// * `targets`: targets to check, all targets if empty
+pub(crate) fn validate(
+    mapping: &MentalMapping,
+    config: &MentalConfig,
+    targets: &[PathBuf],
+) -> Vec<Problem> {
+    let mut problems: Vec<Problem> = Vec::new();
+    let mut problem = |target: &PathBuf, message: String| {
+        problems.push(Problem {
+            target: target.clone(),
+            message,
+        })
+    };
+    let profiles: Vec<Option<String>> = [None]
+        .into_iter()
+        .chain(config.list_profiles().into_iter().map(Some))
+        .collect();
+    let mut files: Vec<PathBuf> = Vec::new();
+
+    for target in targets {
+        if !mapping
+            .mappings
+            .iter()
+            .any(|m| same_target(&m.path, target))
+        {
+            problem(target, "target is not mapped".into());
+        }
+    }
+
+    for m in &mapping.mappings {
+        if !targets.is_empty() && !targets.iter().any(|t| same_target(t, &m.path)) {
+            continue;
+        }
+        if !mapping.target_folder(&m.path).is_dir() {
+            problem(&m.path, "target folder does not exist".into());
+        }
+
+        let file = m.output_file(
+            Path::new(""),
+            m.output_format(OutputFormat::default()),
+            None,
+        );
+        if files.iter().any(|f| same_target(f, &file)) {
+            problem(
+                &m.path,
+                format!("target is mapped more than once into {}", file.display()),
+            );
+        }
+        files.push(file);
+
+        let mut reported: BTreeSet<(String, &str, &str)> = BTreeSet::new();
+        let mut checked: BTreeSet<(&str, String)> = BTreeSet::new();
+        for profile in &profiles {
+            let mut emitted_by: BTreeMap<String, &str> = BTreeMap::new();
+            for component in &m.components {
+                if !config.name_exists(component) {
+                    if profile.is_none() {
+                        problem(&m.path, format!("unknown component '{}'", component));
+                    }
+                    continue;
+                }
+                let names = match config.variable_names(component, profile.as_deref()) {
+                    Ok(names) => names,
+                    Err(error) => {
+                        if profile.is_none() {
+                            problem(&m.path, error.to_string());
+                        }
+                        continue;
+                    }
+                };
+                let suffix = match profile {
+                    Some(profile) => format!(" in profile '{}'", profile),
+                    None => String::new(),
+                };
+                let broken = match config.check_values(component, profile.as_deref()) {
+                    Ok(broken) => broken,
+                    Err(error) => vec![error.to_string()],
+                };
+                for value in broken {
+                    if checked.insert((component.as_str(), value.clone())) {
+                        problem(&m.path, format!("{}{}", value, suffix));
+                    }
+                }
+                for name in names {
+                    match emitted_by.get(&name) {
+                        Some(other) if other != component => {
+                            if !reported.insert((name.clone(), *other, component.as_str())) {
+                                continue;
+                            }
+                            problem(
+                                &m.path,
+                                format!(
+                                    "variable '{}' is emitted by '{}' and '{}'{}",
+                                    name, other, component, suffix
+                                ),
+                            );
+                        }
+                        Some(_) => {}
+                        None => {
+                            emitted_by.insert(name, component);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    problems
+}
+
+/// Fail if there are problems, listing them in the error
+///
+/// * `problems`: problems found by [`validate`]
+pub(crate) fn check(problems: &[Problem]) -> Result<(), MentalError> {
+    if problems.is_empty() {
+        return Ok(());
+    }
+    let listed: Vec<String> = problems.iter().map(|p| format!("  {}", p)).collect();
+    Err(MentalError::Validation(format!(
+        "mapping has {} problems:\n{}",
+        problems.len(),
+        listed.join("\n")
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Validate a mapping against a config with two components emitting `HOST`
+    ///
+    /// * `mapping`: mapping as written in a mapping file
+    /// * `targets`: targets to check
+    fn problems(mapping: &str, targets: &[&str]) -> Vec<String> {
+        let config: MentalConfig = serde_yaml::from_str(
+            r#"
+components:
+  - name: db
+    values:
+      - name: HOST
+        value: db
+      - name: PORT
+        value: 80
+        rules:
+          port: true
+  - name: web
+    values:
+      - name: HOST
+        value: web
+      - name: URL
+        value: not a url
+        rules:
+          url: true
+"#,
+        )
+        .unwrap();
+        let mapping: MentalMapping = serde_yaml::from_str(mapping).unwrap();
+        let targets: Vec<PathBuf> = targets.iter().map(PathBuf::from).collect();
+        validate(&mapping, &config, &targets)
+            .iter()
+            .map(Problem::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn valid_mappings_have_no_problems() {
+        let mapping = "mappings: [{path: src, components: [db]}]";
+        assert!(problems(mapping, &[]).is_empty());
+        assert!(problems(mapping, &["./src/"]).is_empty());
+    }
+
+    #[test]
+    fn requested_targets_have_to_be_mapped() {
+        let mapping = "mappings: [{path: src, components: [db]}]";
+        assert_eq!(
+            problems(mapping, &["missing"]),
+            vec!["missing: target is not mapped"]
+        );
+    }
+
+    #[test]
+    fn problems_of_the_mappings_are_reported() {
+        let mapping = r#"
+mappings:
+  - path: src
+    components: [db, web, cache]
+  - path: ./src
+    components: [db]
+  - path: no-such-folder
+    components: [db]
+"#;
+        assert_eq!(
+            problems(mapping, &[]),
+            vec![
+                "src: URL: value is not a URL",
+                "src: variable 'HOST' is emitted by 'db' and 'web'",
+                "src: unknown component 'cache'",
+                "./src: target is mapped more than once into ./src/.env",
+                "no-such-folder: target folder does not exist",
+            ]
+        );
+    }
+}